@@ -0,0 +1,120 @@
+use coincheck_rust::{
+    decimal::{Price, Size},
+    exchanges::coincheck::{CoincheckWebsocketEvent, OrderBookData},
+    order_book::OrderBook,
+    recorder::Record,
+    replay::read_records,
+};
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use std::{env, path::Path};
+
+// Load the snapshot and the deltas following it from the recording in BENCH_RECORDING,
+// or generate a synthetic session when no recording is given
+fn session() -> (OrderBookData, Vec<OrderBookData>) {
+    match env::var("BENCH_RECORDING") {
+        Ok(path) => recorded_session(Path::new(&path)),
+        Err(_) => synthetic_session(),
+    }
+}
+
+fn recorded_session(path: &Path) -> (OrderBookData, Vec<OrderBookData>) {
+    let mut snapshot = None;
+    let mut deltas = Vec::new();
+    for record in read_records(path).expect("readable recording") {
+        match record.expect("valid record") {
+            Record::Snapshot { data, .. } if snapshot.is_none() => snapshot = Some(data),
+            Record::Message { data, .. } if snapshot.is_some() => {
+                if let Ok(CoincheckWebsocketEvent::OrderBookUpdateEvent(response)) =
+                    serde_json::from_value(data)
+                {
+                    deltas.push(response.1);
+                }
+            }
+            _ => (),
+        }
+    }
+    (snapshot.expect("recording with a snapshot"), deltas)
+}
+
+fn synthetic_session() -> (OrderBookData, Vec<OrderBookData>) {
+    // Small linear congruential generator so every run sees the same session
+    let mut seed: u64 = 42;
+    let mut next = move |range: u64| {
+        seed = seed
+            .wrapping_mul(6_364_136_223_846_793_005)
+            .wrapping_add(1_442_695_040_888_963_407);
+        (seed >> 33) % range
+    };
+
+    let price = |yen: i64| Price::from_units(yen * 100_000_000);
+    let size = |hundredths: i64| Size::from_units(hundredths * 1_000_000);
+
+    let snapshot = OrderBookData {
+        bids: (0..200).map(|i| (price(5_000_000 - i), size(10))).collect(),
+        asks: (0..200).map(|i| (price(5_000_001 + i), size(10))).collect(),
+    };
+    let deltas = (0..5_000)
+        .map(|_| {
+            let mut level = |base: i64, direction: i64| {
+                let hundredths = if next(4) == 0 { 0 } else { next(100) as i64 };
+                (price(base + direction * next(300) as i64), size(hundredths))
+            };
+            OrderBookData {
+                bids: (0..3).map(|_| level(5_000_000, -1)).collect(),
+                asks: (0..3).map(|_| level(5_000_001, 1)).collect(),
+            }
+        })
+        .collect();
+    (snapshot, deltas)
+}
+
+// The previous implementation: a linear scan per level and a full sort of both sides per delta
+fn linear_apply(order_book: &mut OrderBookData, delta: &OrderBookData) -> (Price, Price) {
+    fn apply_side(levels: &mut Vec<(Price, Size)>, delta: &[(Price, Size)], descending: bool) {
+        for delta_level in delta.iter() {
+            match levels.iter_mut().find(|level| level.0 == delta_level.0) {
+                Some(level) => level.1 = delta_level.1,
+                None if !delta_level.1.is_zero() => levels.push(*delta_level),
+                None => (),
+            }
+        }
+        levels.retain(|x| !x.1.is_zero());
+        if descending {
+            levels.sort_by_key(|level| std::cmp::Reverse(level.0));
+        } else {
+            levels.sort_by_key(|level| level.0);
+        }
+    }
+    apply_side(&mut order_book.bids, &delta.bids, true);
+    apply_side(&mut order_book.asks, &delta.asks, false);
+    (order_book.bids[0].0, order_book.asks[0].0)
+}
+
+fn apply_deltas(c: &mut Criterion) {
+    let (snapshot, deltas) = session();
+    let mut group = c.benchmark_group("apply_deltas");
+
+    group.bench_function("linear", |b| {
+        b.iter(|| {
+            let mut order_book = snapshot.clone();
+            for delta in deltas.iter() {
+                black_box(linear_apply(&mut order_book, delta));
+            }
+        })
+    });
+
+    group.bench_function("btree", |b| {
+        b.iter(|| {
+            let mut order_book = OrderBook::from(&snapshot);
+            for delta in deltas.iter() {
+                order_book.apply(delta);
+                black_box((order_book.best_bid_price(), order_book.best_ask_price()));
+            }
+        })
+    });
+
+    group.finish();
+}
+
+criterion_group!(benches, apply_deltas);
+criterion_main!(benches);
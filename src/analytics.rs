@@ -0,0 +1,143 @@
+use crate::{
+    decimal::{Price, Size},
+    order_book::OrderBook,
+};
+
+// Number of levels per side used for depth and imbalance
+pub const DEPTH_LEVELS: usize = 5;
+
+// Side of the book an order would consume
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BookSide {
+    Bid,
+    Ask,
+}
+
+// Summary of the top of the book, recomputed after every delta
+#[derive(Debug, Clone, PartialEq)]
+pub struct BookStats {
+    pub best_bid: (Price, Size),
+    pub best_ask: (Price, Size),
+    pub spread: Price,
+    pub mid: f64,
+    // Best bid and ask weighted by the size on the opposite side
+    pub microprice: f64,
+    // Cumulative size over the top levels, best level first
+    pub bid_depth: Vec<Size>,
+    pub ask_depth: Vec<Size>,
+    // (bid size - ask size) / (bid size + ask size) over the top levels, between -1 and 1
+    pub imbalance: f64,
+}
+
+impl BookStats {
+    // Compute the statistics over the top `levels` levels, or `None` if either side is empty
+    pub fn compute(order_book: &OrderBook, levels: usize) -> Option<BookStats> {
+        let best_bid = order_book.best_bid()?;
+        let best_ask = order_book.best_ask()?;
+
+        let (bid_price, bid_size) = (best_bid.0.to_f64(), best_bid.1.to_f64());
+        let (ask_price, ask_size) = (best_ask.0.to_f64(), best_ask.1.to_f64());
+        let microprice = (bid_price * ask_size + ask_price * bid_size) / (bid_size + ask_size);
+
+        let bid_depth = cumulative_depth(order_book, BookSide::Bid, levels);
+        let ask_depth = cumulative_depth(order_book, BookSide::Ask, levels);
+        let bid_total = bid_depth.last().copied().unwrap_or_default().to_f64();
+        let ask_total = ask_depth.last().copied().unwrap_or_default().to_f64();
+
+        Some(BookStats {
+            best_bid,
+            best_ask,
+            spread: best_ask.0 - best_bid.0,
+            mid: (bid_price + ask_price) / 2.0,
+            microprice,
+            bid_depth,
+            ask_depth,
+            imbalance: (bid_total - ask_total) / (bid_total + ask_total),
+        })
+    }
+
+    pub fn spread_ticks(&self, price_increment: Price) -> i64 {
+        self.spread.ticks(price_increment)
+    }
+}
+
+// Cumulative size over the top `levels` levels of one side, best level first
+pub fn cumulative_depth(order_book: &OrderBook, side: BookSide, levels: usize) -> Vec<Size> {
+    let mut total = Size::ZERO;
+    let cumulate = |(_, size): (Price, Size)| {
+        total += size;
+        total
+    };
+    match side {
+        BookSide::Bid => order_book.bids().take(levels).map(cumulate).collect(),
+        BookSide::Ask => order_book.asks().take(levels).map(cumulate).collect(),
+    }
+}
+
+// Average price of filling `size` against one side of the book, walking down from the best level.
+// Returns `None` if the side does not hold enough size.
+pub fn vwap_to_fill(order_book: &OrderBook, side: BookSide, size: Size) -> Option<f64> {
+    if size <= Size::ZERO {
+        return None;
+    }
+
+    let mut remaining = size;
+    let mut notional = 0.0;
+    let levels: Box<dyn Iterator<Item = (Price, Size)>> = match side {
+        BookSide::Bid => Box::new(order_book.bids()),
+        BookSide::Ask => Box::new(order_book.asks()),
+    };
+    for (price, level_size) in levels {
+        let take = remaining.min(level_size);
+        notional += price.to_f64() * take.to_f64();
+        remaining -= take;
+        if remaining.is_zero() {
+            return Some(notional / size.to_f64());
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::exchanges::coincheck::OrderBookData;
+    use serde_json::json;
+
+    fn order_book() -> OrderBook {
+        let data: OrderBookData = serde_json::from_value(json!({
+            "bids": [["100", "3"], ["99", "1"]],
+            "asks": [["102", "1"], ["103", "2"]],
+        }))
+        .unwrap();
+        OrderBook::from(&data)
+    }
+
+    #[test]
+    fn computes_top_of_book_stats() {
+        let stats = BookStats::compute(&order_book(), 2).unwrap();
+
+        assert_eq!(stats.mid, 101.0);
+        assert_eq!(stats.microprice, 101.5);
+        assert_eq!(stats.spread_ticks(Price::from_f64(1.0)), 2);
+        assert_eq!(
+            stats.bid_depth,
+            vec![Size::from_f64(3.0), Size::from_f64(4.0)]
+        );
+        assert_eq!(stats.imbalance, (4.0 - 3.0) / 7.0);
+    }
+
+    #[test]
+    fn walks_levels_to_fill() {
+        let order_book = order_book();
+
+        assert_eq!(
+            vwap_to_fill(&order_book, BookSide::Ask, Size::from_f64(2.0)),
+            Some(102.5)
+        );
+        assert_eq!(
+            vwap_to_fill(&order_book, BookSide::Ask, Size::from_f64(4.0)),
+            None
+        );
+    }
+}
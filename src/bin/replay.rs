@@ -0,0 +1,38 @@
+use anyhow::{anyhow, Result};
+use coincheck_rust::{
+    exchanges::coincheck,
+    replay::{Replay, Speed},
+};
+use log::info;
+use std::{env, path::Path};
+
+static SYMBOL: &str = "btc_jpy";
+
+// Replay a recording through the trading state, e.g.
+// `cargo run --bin replay -- data/btc_jpy/btc_jpy-2024-01-31-1706659200000.jsonl.gz 10`
+#[tokio::main]
+async fn main() -> Result<()> {
+    env_logger::init();
+
+    let mut args = env::args().skip(1);
+    let path = args
+        .next()
+        .ok_or_else(|| anyhow!("usage: replay <recording> [speed|max]"))?;
+    // Replay in real time unless a speed-up factor or "max" is given
+    let speed = match args.next().as_deref() {
+        None => Speed::RealTime,
+        Some("max") => Speed::Max,
+        Some(factor) => Speed::Accelerated(factor.parse()?),
+    };
+
+    let mut state = coincheck::State::new(SYMBOL)?;
+    let mut replay = Replay::from_file(Path::new(&path), speed)?;
+
+    replay.run(&mut state).await?;
+
+    info!(
+        "replayed until {:?}: best bid {:?}, best ask {:?}, btc balance {}",
+        replay.clock, state.best_bid_price, state.best_ask_price, state.btc_balance
+    );
+    Ok(())
+}
@@ -0,0 +1,129 @@
+use crate::{
+    candle::Candle,
+    exchanges::coincheck::{ExecutionResponse, OrderBookData},
+};
+use log::warn;
+use tokio::sync::broadcast::{self, error::RecvError, error::TryRecvError};
+
+// Connection state of the WebSocket feed
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConnectionState {
+    Connected,
+    Disconnected,
+}
+
+// Event published on the market data bus
+#[derive(Debug, Clone)]
+pub enum MarketEvent {
+    // Raw text message from the WebSocket feed, with the local receive time in milliseconds
+    Message {
+        received_at: u64,
+        text: String,
+    },
+    // Order book delta for a pair
+    OrderBook {
+        pair: String,
+        order_book: OrderBookData,
+    },
+    // Trades received on the trades channel
+    Trades(ExecutionResponse),
+    // Candle completed by a candle aggregator
+    Candle(Candle),
+    // Change of the WebSocket connection state
+    Connection(ConnectionState),
+    // Delivered to a subscriber that fell behind, with the number of events it missed
+    Lagged(u64),
+    // The process is about to exit; subscribers should finish their work and return
+    Shutdown,
+}
+
+// Broadcast bus fanning out market data to any number of subscribers
+#[derive(Clone)]
+pub struct Bus {
+    sender: broadcast::Sender<MarketEvent>,
+}
+
+impl Bus {
+    // Create a bus keeping up to `capacity` events buffered for slow subscribers
+    pub fn new(capacity: usize) -> Bus {
+        let (sender, _) = broadcast::channel(capacity);
+        Bus { sender }
+    }
+
+    // Publish an event and return the number of subscribers it was delivered to
+    pub fn publish(&self, event: MarketEvent) -> usize {
+        // Sending only fails when nobody is subscribed, which is not an error for the publisher
+        self.sender.send(event).unwrap_or(0)
+    }
+
+    pub fn subscribe(&self, name: &str) -> Subscriber {
+        Subscriber {
+            name: name.to_string(),
+            receiver: self.sender.subscribe(),
+            lagged: 0,
+        }
+    }
+
+    pub fn subscriber_count(&self) -> usize {
+        self.sender.receiver_count()
+    }
+}
+
+pub struct Subscriber {
+    pub name: String,
+    receiver: broadcast::Receiver<MarketEvent>,
+    // Total number of events this subscriber missed because it fell behind
+    pub lagged: u64,
+}
+
+impl Subscriber {
+    // Wait for the next event. Returns `None` once every publisher is gone.
+    pub async fn recv(&mut self) -> Option<MarketEvent> {
+        match self.receiver.recv().await {
+            Ok(event) => Some(event),
+            Err(RecvError::Lagged(skipped)) => Some(self.lag(skipped)),
+            Err(RecvError::Closed) => None,
+        }
+    }
+
+    // Return the next event if one is already buffered
+    pub fn try_recv(&mut self) -> Option<MarketEvent> {
+        match self.receiver.try_recv() {
+            Ok(event) => Some(event),
+            Err(TryRecvError::Lagged(skipped)) => Some(self.lag(skipped)),
+            Err(TryRecvError::Empty) | Err(TryRecvError::Closed) => None,
+        }
+    }
+
+    fn lag(&mut self, skipped: u64) -> MarketEvent {
+        self.lagged += skipped;
+        warn!(
+            "subscriber {} lagged behind and missed {} events ({} in total)",
+            self.name, skipped, self.lagged
+        );
+        MarketEvent::Lagged(skipped)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn slow_subscriber_is_told_it_lagged() {
+        let bus = Bus::new(2);
+        let mut fast = bus.subscribe("fast");
+        let mut slow = bus.subscribe("slow");
+
+        for _ in 0..3 {
+            bus.publish(MarketEvent::Connection(ConnectionState::Connected));
+            assert!(matches!(fast.try_recv(), Some(MarketEvent::Connection(_))));
+        }
+
+        assert!(matches!(slow.try_recv(), Some(MarketEvent::Lagged(1))));
+        assert_eq!(slow.lagged, 1);
+        assert!(matches!(slow.try_recv(), Some(MarketEvent::Connection(_))));
+        assert!(matches!(slow.try_recv(), Some(MarketEvent::Connection(_))));
+        assert!(slow.try_recv().is_none());
+    }
+}
@@ -0,0 +1,287 @@
+use crate::{
+    bus::{Bus, MarketEvent, Subscriber},
+    decimal::{Price, Size},
+    exchanges::coincheck::{get_trades, CoincheckWebsocketEvent, Execution},
+    recorder::Record,
+    util::{get_timestamp, timestamp_from_iso8601},
+};
+use anyhow::Result;
+use log::{debug, warn};
+use reqwest::Client;
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+
+// OHLCV bar covering [start, start + interval) in milliseconds since the Unix epoch
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Candle {
+    pub pair: String,
+    pub start: u64,
+    pub interval: u64,
+    pub open: Price,
+    pub high: Price,
+    pub low: Price,
+    pub close: Price,
+    pub volume: Size,
+    pub trades: usize,
+}
+
+impl Candle {
+    fn new(pair: &str, start: u64, interval: u64, price: Price) -> Candle {
+        Candle {
+            pair: pair.to_string(),
+            start,
+            interval,
+            open: price,
+            high: price,
+            low: price,
+            close: price,
+            volume: Size::ZERO,
+            trades: 0,
+        }
+    }
+
+    pub fn end(&self) -> u64 {
+        self.start + self.interval
+    }
+}
+
+// Builds candles of a fixed interval from trades of one pair. Intervals without trades
+// produce flat candles at the previous close with zero volume.
+pub struct CandleAggregator {
+    pub pair: String,
+    interval: u64,
+    current: Option<Candle>,
+    // Highest trade id already counted by a backfill. Live trades up to it are skipped, since
+    // trades arriving while the backfill runs are in both.
+    backfilled_until: Option<usize>,
+}
+
+impl CandleAggregator {
+    pub fn new(pair: &str, interval: Duration) -> CandleAggregator {
+        CandleAggregator {
+            pair: pair.to_string(),
+            interval: (interval.as_millis() as u64).max(1),
+            current: None,
+            backfilled_until: None,
+        }
+    }
+
+    // Add a trade and return the candles it completed, oldest first
+    pub fn add(&mut self, timestamp: u64, price: Price, size: Size) -> Vec<Candle> {
+        let completed = self.close_until(timestamp);
+        let start = timestamp - timestamp % self.interval;
+
+        match &mut self.current {
+            // Late trades for an already completed candle are dropped
+            Some(candle) if start < candle.start => {
+                debug!("dropping late trade at {} for {}", timestamp, self.pair);
+                return completed;
+            }
+            // The first trade of an interval sets its open
+            Some(candle) if candle.trades == 0 => {
+                *candle = Candle::new(&self.pair, candle.start, self.interval, price);
+                candle.volume = size;
+                candle.trades = 1;
+            }
+            Some(candle) => {
+                candle.high = candle.high.max(price);
+                candle.low = candle.low.min(price);
+                candle.close = price;
+                candle.volume += size;
+                candle.trades += 1;
+            }
+            None => {
+                let mut candle = Candle::new(&self.pair, start, self.interval, price);
+                candle.volume = size;
+                candle.trades = 1;
+                self.current = Some(candle);
+            }
+        }
+        completed
+    }
+
+    pub fn add_execution(&mut self, execution: &Execution) -> Vec<Candle> {
+        if execution.pair != self.pair || self.backfilled_until.is_some_and(|id| execution.id <= id)
+        {
+            return Vec::new();
+        }
+        self.add(execution.timestamp, execution.price, execution.size)
+    }
+
+    fn mark_backfilled(&mut self, id: usize) {
+        self.backfilled_until = Some(self.backfilled_until.map_or(id, |until| until.max(id)));
+    }
+
+    // Complete every candle that ended at or before `now`, so quiet intervals are emitted on time
+    pub fn close_until(&mut self, now: u64) -> Vec<Candle> {
+        let mut completed = Vec::new();
+        while let Some(candle) = self.current.take() {
+            if candle.end() > now {
+                self.current = Some(candle);
+                break;
+            }
+            // Carry the close into the next interval, which starts flat until a trade arrives
+            let next = Candle::new(&self.pair, candle.end(), self.interval, candle.close);
+            completed.push(candle);
+            self.current = Some(next);
+        }
+        completed
+    }
+
+    // Build candles from the trades in a recording
+    pub fn backfill_from_records(&mut self, records: &[Record]) -> Vec<Candle> {
+        let mut completed = Vec::new();
+        for record in records {
+            if let Record::Message { data, .. } = record {
+                if let Ok(CoincheckWebsocketEvent::ExecutionEvent(executions)) =
+                    serde_json::from_value(data.clone())
+                {
+                    for execution in executions.iter().filter_map(|e| Execution::parse(e).ok()) {
+                        completed.extend(self.add_execution(&execution));
+                        if execution.pair == self.pair {
+                            self.mark_backfilled(execution.id);
+                        }
+                    }
+                }
+            }
+        }
+        completed
+    }
+
+    // Build candles from the most recent trades of the public trades endpoint
+    pub async fn backfill_from_api(
+        &mut self,
+        client: &Client,
+        limit: usize,
+    ) -> Result<Vec<Candle>> {
+        let mut completed = Vec::new();
+        for trade in get_trades::get_trades(client, &self.pair, limit).await? {
+            let timestamp = timestamp_from_iso8601(&trade.created_at)?;
+            completed.extend(self.add(timestamp, trade.price, trade.size));
+            self.mark_backfilled(trade.id);
+        }
+        Ok(completed)
+    }
+}
+
+// Time in milliseconds a candle is kept open after its interval ended, for trades still in flight
+const CLOSE_DELAY_MS: u64 = 2_000;
+
+// Aggregate trades from the bus into candles and publish every completed candle on the bus
+pub async fn run(
+    mut subscriber: Subscriber,
+    bus: Bus,
+    mut aggregator: CandleAggregator,
+    client: Client,
+) -> Result<()> {
+    match aggregator.backfill_from_api(&client, 100).await {
+        Ok(candles) => {
+            for candle in candles {
+                bus.publish(MarketEvent::Candle(candle));
+            }
+        }
+        Err(e) => warn!(
+            "failed to backfill candles for {}: {:?}",
+            aggregator.pair, e
+        ),
+    }
+
+    let mut close_interval = tokio::time::interval(Duration::from_secs(1));
+    loop {
+        let completed = tokio::select! {
+            event = subscriber.recv() => match event {
+                Some(MarketEvent::Trades(executions)) => executions
+                    .iter()
+                    .filter_map(|e| Execution::parse(e).ok())
+                    .flat_map(|execution| aggregator.add_execution(&execution))
+                    .collect(),
+                Some(_) => Vec::new(),
+                None => break,
+            },
+            // Give trades a moment to arrive before closing a quiet interval
+            _ = close_interval.tick() => {
+                aggregator.close_until(get_timestamp()?.saturating_sub(CLOSE_DELAY_MS))
+            }
+        };
+        for candle in completed {
+            debug!("candle: {:?}", candle);
+            bus.publish(MarketEvent::Candle(candle));
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn price(value: f64) -> Price {
+        Price::from_f64(value)
+    }
+
+    fn size(value: f64) -> Size {
+        Size::from_f64(value)
+    }
+
+    #[test]
+    fn aggregates_trades_and_fills_empty_intervals() {
+        let mut aggregator = CandleAggregator::new("btc_jpy", Duration::from_secs(60));
+
+        assert!(aggregator.add(1_000, price(100.0), size(1.0)).is_empty());
+        assert!(aggregator.add(2_000, price(105.0), size(1.0)).is_empty());
+        assert!(aggregator.add(3_000, price(95.0), size(2.0)).is_empty());
+        let completed = aggregator.add(150_000, price(110.0), size(1.0));
+
+        assert_eq!(completed.len(), 2);
+        assert_eq!(
+            (completed[0].open, completed[0].high, completed[0].low),
+            (price(100.0), price(105.0), price(95.0))
+        );
+        assert_eq!(completed[0].close, price(95.0));
+        assert_eq!(completed[0].volume, size(4.0));
+        assert_eq!(completed[1].start, 60_000);
+        assert_eq!(completed[1].open, price(95.0));
+        assert_eq!(completed[1].volume, Size::ZERO);
+
+        let completed = aggregator.close_until(180_000);
+        assert_eq!(completed.len(), 1);
+        assert_eq!(completed[0].open, price(110.0));
+        assert_eq!(completed[0].close, price(110.0));
+        assert_eq!(completed[0].trades, 1);
+    }
+
+    #[test]
+    fn backfills_from_records_and_skips_repeated_trades() {
+        let trade = |id: &str, timestamp: &str, amount: &str| {
+            serde_json::json!([[timestamp, id, "btc_jpy", "100", amount, "buy", "1", "2"]])
+        };
+        let records = vec![
+            Record::Message {
+                received_at: 1_000,
+                data: trade("1", "1", "1.0"),
+            },
+            Record::Message {
+                received_at: 2_000,
+                data: trade("2", "2", "2.0"),
+            },
+            Record::Message {
+                received_at: 2_000,
+                data: serde_json::json!(["btc_jpy", { "bids": [], "asks": [] }]),
+            },
+        ];
+        let mut aggregator = CandleAggregator::new("btc_jpy", Duration::from_secs(60));
+        assert!(aggregator.backfill_from_records(&records).is_empty());
+
+        // The second trade arrives live as well and is not counted again
+        let execution = |id: &str, amount: &str| {
+            let data = trade(id, "3", amount);
+            Execution::parse(&serde_json::from_value::<Vec<[String; 8]>>(data).unwrap()[0]).unwrap()
+        };
+        aggregator.add_execution(&execution("2", "2.0"));
+        aggregator.add_execution(&execution("3", "0.5"));
+        let completed = aggregator.close_until(60_000);
+        assert_eq!(completed.len(), 1);
+        assert_eq!(completed[0].trades, 3);
+        assert_eq!(completed[0].volume, size(3.5));
+    }
+}
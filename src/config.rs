@@ -1,13 +1,92 @@
-use anyhow::Result;
+use crate::{
+    decimal::{Price, Size},
+    placement::{Ladder, QuoteRules},
+    rebalancer::{RebalanceExecution, RebalanceRules},
+    volatility::VolatilityRules,
+};
+use anyhow::{anyhow, Error, Result};
+use std::{str::FromStr, time::Duration};
+
+// Quoting strategies that can be selected in the config
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum StrategyKind {
+    // Join the best bid or ask depending on the BTC balance
+    #[default]
+    JoinBest,
+    // Quote both sides, sized to keep the inventory within its bands
+    InventoryBands,
+    // Quote around a reservation price skewed by inventory and volatility
+    AvellanedaStoikov,
+}
+
+impl FromStr for StrategyKind {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<StrategyKind> {
+        match s {
+            "join_best" => Ok(StrategyKind::JoinBest),
+            "inventory_bands" => Ok(StrategyKind::InventoryBands),
+            "avellaneda_stoikov" => Ok(StrategyKind::AvellanedaStoikov),
+            _ => Err(anyhow!("unknown strategy: {:?}", s)),
+        }
+    }
+}
+
+// Parameters of the Avellaneda-Stoikov strategy. Inventory is measured in multiples of the
+// order size and prices in quote currency.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct AvellanedaStoikovParams {
+    // Risk aversion (gamma): how strongly inventory skews the reservation price and widens quotes
+    pub risk_aversion: f64,
+    // Order book liquidity (k): how fast the fill probability decays with distance from the mid
+    pub liquidity: f64,
+    // Time over which the inventory risk is carried
+    pub horizon: Duration,
+    // Window of trades used to estimate the volatility
+    pub volatility_window: Duration,
+}
+
+impl Default for AvellanedaStoikovParams {
+    fn default() -> AvellanedaStoikovParams {
+        AvellanedaStoikovParams {
+            risk_aversion: 0.00002,
+            liquidity: 0.002,
+            horizon: Duration::from_secs(60),
+            volatility_window: Duration::from_secs(5 * 60),
+        }
+    }
+}
+
+// When a live order is replaced by the quote the strategy wants instead
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RequoteRules {
+    // Ticks an order may sit behind its desired price, no longer at the top, before it is moved
+    pub passive_ticks: i64,
+    // Ticks an order may sit ahead of its desired price, too aggressive, before it is moved
+    pub aggressive_ticks: i64,
+    pub size_tolerance: Size,
+    // Orders are not moved before they have rested this long
+    pub min_order_age: Duration,
+}
 
 pub struct Config {
     pub symbol: String,
-    pub size: f64,
-    pub max_size: f64,
-    pub price_increment: f64,
-    pub price_base: f64,
-    pub size_increment: f64,
-    pub size_base: f64,
+    pub size: Size,
+    // Inventory bands: never buy above `max_size` or sell below `min_inventory`
+    pub max_size: Size,
+    pub min_inventory: Size,
+    pub target_inventory: Size,
+    pub price_increment: Price,
+    pub size_increment: Size,
+    pub strategy: StrategyKind,
+    pub quote_rules: QuoteRules,
+    // Orders per side of the two-sided strategies
+    pub ladder: Ladder,
+    pub avellaneda_stoikov: AvellanedaStoikovParams,
+    pub requote: RequoteRules,
+    pub rebalance: RebalanceRules,
+    // Widen, shrink and pause quotes as volatility rises; `None` disables it
+    pub volatility: Option<VolatilityRules>,
 }
 
 impl Config {
@@ -17,14 +96,40 @@ impl Config {
         price_increment: f64,
         size_increment: f64,
     ) -> Result<Config> {
+        let size_increment = Size::from_f64(size_increment);
+        let size = Size::from_f64(size).round_to(size_increment);
         Ok(Config {
             symbol: symbol.to_string(),
             size,
-            max_size: size * 1.0,
-            price_increment,
-            price_base: 1.0 / price_increment,
+            max_size: size,
+            min_inventory: Size::ZERO,
+            target_inventory: Size::ZERO,
+            price_increment: Price::from_f64(price_increment),
             size_increment,
-            size_base: 1.0 / size_increment,
+            strategy: StrategyKind::default(),
+            quote_rules: QuoteRules::default(),
+            ladder: Ladder::default(),
+            avellaneda_stoikov: AvellanedaStoikovParams::default(),
+            volatility: None,
+            rebalance: RebalanceRules {
+                hard_min: Size::ZERO,
+                hard_max: size.times(2),
+                slice_size: size,
+                slice_interval: Duration::from_secs(2),
+                execution: RebalanceExecution::AggressiveLimit { ticks: 0 },
+            },
+            requote: RequoteRules {
+                passive_ticks: 0,
+                aggressive_ticks: 0,
+                // Partial fills shrink an order; keep it rather than losing its place in the queue
+                size_tolerance: size,
+                min_order_age: Duration::ZERO,
+            },
         })
     }
+
+    // Round a size to the nearest valid increment
+    pub fn round_size(&self, size: Size) -> Size {
+        size.round_to(self.size_increment)
+    }
 }
@@ -0,0 +1,309 @@
+use anyhow::{anyhow, Error};
+use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
+use std::{
+    fmt,
+    iter::Sum,
+    ops::{Add, AddAssign, Neg, Sub, SubAssign},
+    str::FromStr,
+};
+
+// Number of decimal places kept by the fixed-point types. Coincheck never uses more than 8.
+pub const DECIMALS: u32 = 8;
+const SCALE: i64 = 10_i64.pow(DECIMALS);
+
+// Defines a fixed-point decimal stored as an integer number of 10^-8 units, so values parsed
+// from the API are exact and never pick up floating-point error on their way back into orders
+macro_rules! fixed_point {
+    ($name:ident) => {
+        #[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+        pub struct $name(i64);
+
+        impl $name {
+            pub const ZERO: $name = $name(0);
+            pub const ONE: $name = $name(SCALE);
+
+            pub const fn from_units(units: i64) -> $name {
+                $name(units)
+            }
+
+            pub const fn units(self) -> i64 {
+                self.0
+            }
+
+            // Convert from a float, rounding to the nearest unit
+            pub fn from_f64(value: f64) -> $name {
+                $name((value * SCALE as f64).round() as i64)
+            }
+
+            // Lossy conversion for statistics; never feed the result back into an order
+            pub fn to_f64(self) -> f64 {
+                self.0 as f64 / SCALE as f64
+            }
+
+            pub fn is_zero(self) -> bool {
+                self.0 == 0
+            }
+
+            pub fn abs(self) -> $name {
+                $name(self.0.abs())
+            }
+
+            // Round to the nearest multiple of `tick`, halves away from zero
+            pub fn round_to(self, tick: $name) -> $name {
+                if tick.0 <= 0 {
+                    return self;
+                }
+                let half = tick.0 / 2;
+                let ticks = if self.0 >= 0 {
+                    (self.0 + half) / tick.0
+                } else {
+                    (self.0 - half) / tick.0
+                };
+                $name(ticks * tick.0)
+            }
+
+            // Round down to a multiple of `tick`
+            pub fn floor_to(self, tick: $name) -> $name {
+                if tick.0 <= 0 {
+                    return self;
+                }
+                $name(self.0.div_euclid(tick.0) * tick.0)
+            }
+
+            // Round up to a multiple of `tick`
+            pub fn ceil_to(self, tick: $name) -> $name {
+                if tick.0 <= 0 {
+                    return self;
+                }
+                $name(-((-self.0).div_euclid(tick.0)) * tick.0)
+            }
+
+            // Number of whole ticks in this value
+            pub fn ticks(self, tick: $name) -> i64 {
+                if tick.0 <= 0 {
+                    return 0;
+                }
+                self.0 / tick.0
+            }
+
+            // Multiply by a whole number of units, e.g. a number of ticks
+            pub fn times(self, factor: i64) -> $name {
+                $name(self.0 * factor)
+            }
+        }
+
+        impl Add for $name {
+            type Output = $name;
+
+            fn add(self, other: $name) -> $name {
+                $name(self.0 + other.0)
+            }
+        }
+
+        impl Sub for $name {
+            type Output = $name;
+
+            fn sub(self, other: $name) -> $name {
+                $name(self.0 - other.0)
+            }
+        }
+
+        impl AddAssign for $name {
+            fn add_assign(&mut self, other: $name) {
+                self.0 += other.0;
+            }
+        }
+
+        impl SubAssign for $name {
+            fn sub_assign(&mut self, other: $name) {
+                self.0 -= other.0;
+            }
+        }
+
+        impl Neg for $name {
+            type Output = $name;
+
+            fn neg(self) -> $name {
+                $name(-self.0)
+            }
+        }
+
+        impl Sum for $name {
+            fn sum<I: Iterator<Item = $name>>(iter: I) -> $name {
+                iter.fold($name::ZERO, Add::add)
+            }
+        }
+
+        impl FromStr for $name {
+            type Err = Error;
+
+            fn from_str(s: &str) -> Result<$name, Error> {
+                parse_units(s).map($name)
+            }
+        }
+
+        impl fmt::Display for $name {
+            fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                format_units(self.0, f)
+            }
+        }
+
+        // Serialized as a decimal string, the format Coincheck uses for rates and amounts
+        impl Serialize for $name {
+            fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+            where
+                S: Serializer,
+            {
+                serializer.collect_str(self)
+            }
+        }
+
+        // Deserialized from either a decimal string or a number
+        impl<'de> Deserialize<'de> for $name {
+            fn deserialize<D>(deserializer: D) -> Result<$name, D::Error>
+            where
+                D: Deserializer<'de>,
+            {
+                #[derive(Deserialize)]
+                #[serde(untagged)]
+                enum StringOrFloat {
+                    String(String),
+                    Float(f64),
+                }
+
+                match StringOrFloat::deserialize(deserializer)? {
+                    StringOrFloat::String(s) => s.parse().map_err(de::Error::custom),
+                    StringOrFloat::Float(f) => Ok($name::from_f64(f)),
+                }
+            }
+        }
+    };
+}
+
+fixed_point!(Price);
+fixed_point!(Size);
+
+impl Price {
+    // Value of `size` at this price, rounded up to a multiple of `tick`. The product is taken in
+    // i128 so it stays exact.
+    pub fn notional_ceil(self, size: Size, tick: Price) -> Result<Price, Error> {
+        let product = self.0 as i128 * size.0 as i128;
+        let step = tick.0.max(1) as i128 * SCALE as i128;
+        let steps = -((-product).div_euclid(step));
+        i64::try_from(steps * tick.0.max(1) as i128)
+            .map(Price)
+            .map_err(|_| anyhow!("notional out of range: {} x {}", self, size))
+    }
+}
+
+// Parse a decimal string such as "-12.345" into 10^-8 units without going through a float
+fn parse_units(s: &str) -> Result<i64, Error> {
+    let (negative, digits) = match s.strip_prefix('-') {
+        Some(rest) => (true, rest),
+        None => (false, s.strip_prefix('+').unwrap_or(s)),
+    };
+    let (integer, fraction) = digits.split_once('.').unwrap_or((digits, ""));
+    if integer.is_empty() && fraction.is_empty() {
+        return Err(anyhow!("invalid decimal: {:?}", s));
+    }
+    if !integer
+        .chars()
+        .chain(fraction.chars())
+        .all(|c| c.is_ascii_digit())
+    {
+        return Err(anyhow!("invalid decimal: {:?}", s));
+    }
+
+    // Digits beyond the supported precision must be zeros
+    let fraction = fraction.trim_end_matches('0');
+    if fraction.len() > DECIMALS as usize {
+        return Err(anyhow!("too many decimal places: {:?}", s));
+    }
+
+    let integer: i64 = if integer.is_empty() {
+        0
+    } else {
+        integer.parse()?
+    };
+    let fraction: i64 = if fraction.is_empty() {
+        0
+    } else {
+        format!("{:0<width$}", fraction, width = DECIMALS as usize).parse()?
+    };
+
+    let units = integer
+        .checked_mul(SCALE)
+        .and_then(|units| units.checked_add(fraction))
+        .ok_or_else(|| anyhow!("decimal out of range: {:?}", s))?;
+    Ok(if negative { -units } else { units })
+}
+
+fn format_units(units: i64, f: &mut fmt::Formatter) -> fmt::Result {
+    let sign = if units < 0 { "-" } else { "" };
+    let integer = units.unsigned_abs() / SCALE as u64;
+    let fraction = units.unsigned_abs() % SCALE as u64;
+    if fraction == 0 {
+        write!(f, "{}{}", sign, integer)
+    } else {
+        let fraction = format!("{:0width$}", fraction, width = DECIMALS as usize);
+        write!(f, "{}{}.{}", sign, integer, fraction.trim_end_matches('0'))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_and_formats_exactly() -> anyhow::Result<()> {
+        let size: Size = "0.1".parse()?;
+        let total = size + "0.2".parse()?;
+        assert_eq!(total, "0.3".parse()?);
+        assert_eq!(total.to_string(), "0.3");
+        assert_eq!("5000000.0".parse::<Price>()?.to_string(), "5000000");
+        assert_eq!("-0.00000001".parse::<Size>()?, Size::from_units(-1));
+        assert!("0.000000001".parse::<Size>().is_err());
+        assert!("1e5".parse::<Price>().is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn computes_notional_exactly() -> anyhow::Result<()> {
+        let price: Price = "5000000".parse()?;
+        let size: Size = "0.00500001".parse()?;
+        assert_eq!(price.notional_ceil(size, Price::ONE)?, "25001".parse()?);
+        assert_eq!(
+            price.notional_ceil("0.005".parse()?, Price::ONE)?,
+            "25000".parse()?
+        );
+        assert_eq!(
+            "0.1"
+                .parse::<Price>()?
+                .notional_ceil("0.3".parse()?, "0.01".parse()?)?,
+            "0.03".parse()?
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn rounds_to_ticks() -> anyhow::Result<()> {
+        let tick: Price = "5".parse()?;
+        let price: Price = "12.5".parse()?;
+        assert_eq!(price.round_to(tick), "15".parse()?);
+        assert_eq!(price.floor_to(tick), "10".parse()?);
+        assert_eq!(price.ceil_to(tick), "15".parse()?);
+        assert_eq!((-price).floor_to(tick), "-15".parse()?);
+        assert_eq!(price.ticks(tick), 2);
+        Ok(())
+    }
+
+    #[test]
+    fn deserializes_strings_and_numbers() -> anyhow::Result<()> {
+        let levels: Vec<(Price, Size)> =
+            serde_json::from_str(r#"[["5000000.0", "0.005"], [4999999, 0.1]]"#)?;
+        assert_eq!(levels[0], ("5000000".parse()?, "0.005".parse()?));
+        assert_eq!(levels[1], ("4999999".parse()?, "0.1".parse()?));
+        assert_eq!(serde_json::to_string(&levels[0])?, r#"["5000000","0.005"]"#);
+        Ok(())
+    }
+}
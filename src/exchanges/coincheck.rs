@@ -1,14 +1,36 @@
 pub mod cancel_order;
+pub mod get_accounts;
 pub mod get_active_orders;
 pub mod get_balance;
 pub mod get_order_books;
+pub mod get_trades;
+pub mod get_transactions;
 pub mod place_order;
+pub mod subscriptions;
 
-use crate::{config::Config, string_or_float, vec_string_or_float};
+use crate::{
+    analytics::{BookStats, DEPTH_LEVELS},
+    config::Config,
+    decimal::{Price, Size},
+    fees::Fees,
+    integrity::{IntegrityCheck, Verdict},
+    order_book::{BookHealth, CompetingView, OrderBook},
+    pnl::Pnl,
+    queue_position::{QueuePosition, QueueTracker},
+    rebalancer::Rebalancer,
+    reconciler::{Action, Reconciler},
+    strategy::{Quote, Side, Snapshot, Strategy},
+    trade_tape::{TradeStats, TradeTape},
+    util::get_timestamp,
+};
 use anyhow::Result;
-use log::debug;
+use log::{debug, info, warn};
 use reqwest::Client;
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::HashSet,
+    time::{Duration, Instant},
+};
 
 #[derive(Debug, Deserialize, Clone)]
 #[serde(untagged)]
@@ -19,29 +41,68 @@ pub enum CoincheckWebsocketEvent {
 
 pub type ExecutionResponse = Vec<[String; 8]>;
 
+// A trade from the trades channel:
+// [timestamp, trade id, pair, rate, amount, taker side, taker order id, maker order id]
 #[derive(Debug, Deserialize, Clone)]
 pub struct Execution {
-    pub size: f64,
-    pub maker_id: String,
+    // Exchange time in milliseconds since the Unix epoch
+    pub timestamp: u64,
+    pub id: usize,
+    pub pair: String,
+    pub price: Price,
+    pub size: Size,
+    pub taker_side: String,
+    pub taker_id: usize,
+    pub maker_id: usize,
 }
 
-#[derive(Debug, Deserialize, Clone)]
-pub struct OrderBookResponse(pub String, pub OrderBook);
+impl Execution {
+    pub fn parse(exec: &[String; 8]) -> Result<Execution> {
+        Ok(Execution {
+            // Sent in seconds, possibly with a fractional part
+            timestamp: (exec[0].parse::<f64>()? * 1000.0).round() as u64,
+            id: exec[1].parse()?,
+            pair: exec[2].clone(),
+            price: exec[3].parse()?,
+            size: exec[4].parse()?,
+            taker_side: exec[5].clone(),
+            taker_id: exec[6].parse()?,
+            maker_id: exec[7].parse()?,
+        })
+    }
+}
+
+// Whether our order provided or took liquidity in a fill
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Liquidity {
+    Maker,
+    Taker,
+}
+
+// A fill of one of our orders
+#[derive(Debug, Clone)]
+pub struct Fill {
+    pub order_id: usize,
+    pub side: String,
+    pub price: Price,
+    pub size: Size,
+    pub liquidity: Liquidity,
+}
 
 #[derive(Debug, Deserialize, Clone)]
-pub struct OrderBook {
-    #[serde(with = "vec_string_or_float")]
-    pub bids: Vec<[f64; 2]>,
-    #[serde(with = "vec_string_or_float")]
-    pub asks: Vec<[f64; 2]>,
+pub struct OrderBookResponse(pub String, pub OrderBookData);
+
+// Order book levels as sent by Coincheck, either a full snapshot or a delta
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct OrderBookData {
+    pub bids: Vec<(Price, Size)>,
+    pub asks: Vec<(Price, Size)>,
 }
 
 #[derive(Debug, Deserialize, Clone)]
 pub struct Ask {
-    #[serde(with = "string_or_float")]
-    pub price: f64,
-    #[serde(with = "string_or_float")]
-    pub size: f64,
+    pub price: Price,
+    pub size: Size,
 }
 
 #[derive(Debug, Clone, Deserialize)]
@@ -50,239 +111,665 @@ pub struct Order {
     #[serde(alias = "order_type")]
     pub side: String,
     #[serde(alias = "rate")]
-    #[serde(with = "string_or_float")]
-    pub price: f64,
+    pub price: Price,
     #[serde(alias = "amount")]
     #[serde(alias = "pending_amount")]
-    #[serde(with = "string_or_float")]
-    pub size: f64,
+    pub size: Size,
+    // When we placed the order, unknown for orders found on the exchange
+    #[serde(skip)]
+    pub placed_at: Option<Instant>,
+}
+
+// Counts of our order activity, to keep an eye on cancel churn
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct OrderCounters {
+    pub placed: u64,
+    pub cancelled: u64,
+    // Orders filled at least partly; several partial fills of one order count once
+    filled_orders: HashSet<usize>,
 }
 
+impl OrderCounters {
+    pub fn on_fill(&mut self, order_id: usize) {
+        self.filled_orders.insert(order_id);
+    }
+
+    // Number of distinct orders that were filled fully or partly
+    pub fn fills(&self) -> u64 {
+        self.filled_orders.len() as u64
+    }
+
+    pub fn cancels_per_fill(&self) -> Option<f64> {
+        let fills = self.fills();
+        (fills > 0).then(|| self.cancelled as f64 / fills as f64)
+    }
+}
+
+// Outcome of a quoting pass
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct QuotePass {
+    // Whether any order was actually cancelled or placed
+    pub progressed: bool,
+    // When the first order request of the pass was sent
+    pub first_sent_at: Option<Instant>,
+    // End of the backoff that held back or rejected an order, when another pass is due
+    pub retry_at: Option<Instant>,
+}
+
+// How long trades are kept on the trade tape
+pub const TRADE_TAPE_HORIZON: Duration = Duration::from_secs(15 * 60);
+
+// Minimum time between resyncs triggered by a crossed or stale locked book
+pub const BOOK_RESYNC_INTERVAL: Duration = Duration::from_secs(5);
+
+// How long the book may stay locked before the lock is taken for a missed update
+pub const LOCKED_RESYNC_DELAY: Duration = Duration::from_secs(2);
+
+// How long new orders are held back after the exchange rejected one
+pub const REJECTED_ORDER_BACKOFF: Duration = Duration::from_secs(1);
+
+// Minimum order size on Coincheck (0.005 BTC)
+pub const MIN_ORDER_SIZE: Size = Size::from_units(500_000);
+
 pub struct State {
     pub symbol: String,
-    pub btc_balance: f64,
-    pub buy_order: Option<Order>,
-    pub sell_order: Option<Order>,
+    pub btc_balance: Size,
+    // Our live orders per side, in the order they were placed
+    pub buy_orders: Vec<Order>,
+    pub sell_orders: Vec<Order>,
+    // Market orders that may still be executing. They never rest in the book, so they are kept
+    // apart from the live orders and only used to attribute fills.
+    pub market_orders: Vec<Order>,
     pub order_book: Option<OrderBook>,
-    pub best_ask_price: Option<f64>,
-    pub best_bid_price: Option<f64>,
+    pub best_ask_price: Option<Price>,
+    pub best_bid_price: Option<Price>,
+    pub book_stats: Option<BookStats>,
+    pub book_health: BookHealth,
+    // Since when the book has been locked
+    pub locked_since: Option<Instant>,
+    pub last_resync: Option<Instant>,
+    // No orders are placed before this time after a rejection
+    pub place_backoff_until: Option<Instant>,
+    pub trade_tape: TradeTape,
+    pub queue: QueueTracker,
+    pub counters: OrderCounters,
+    pub fees: Fees,
+    pub pnl: Pnl,
+    // Start of the session in milliseconds since the Unix epoch
+    pub started_at: u64,
+}
+
+// Apply an execution to one of our orders if we were on either side of it
+fn fill_order(orders: &mut Vec<Order>, exec: &Execution) -> Option<Fill> {
+    // Our order rested in the book, or it crossed the spread and took liquidity
+    let (index, liquidity) = orders.iter().enumerate().find_map(|(index, order)| {
+        if order.id == exec.maker_id {
+            Some((index, Liquidity::Maker))
+        } else if order.id == exec.taker_id {
+            Some((index, Liquidity::Taker))
+        } else {
+            None
+        }
+    })?;
+    let order = &mut orders[index];
+
+    let fill = Fill {
+        order_id: order.id,
+        side: order.side.clone(),
+        price: exec.price,
+        size: exec.size,
+        liquidity,
+    };
+    debug!("fill: {:?}", fill);
+
+    if exec.size >= order.size {
+        // If the order is completely filled, remove it from the state
+        orders.remove(index);
+    } else {
+        // Otherwise reduce the size of the order by the filled amount
+        order.size -= exec.size;
+    }
+    Some(fill)
 }
 
 impl State {
     pub fn new(symbols: &str) -> Result<State> {
         Ok(State {
             symbol: symbols.to_string(),
-            btc_balance: 0.0,
-            buy_order: None,
-            sell_order: None,
+            btc_balance: Size::ZERO,
+            buy_orders: Vec::new(),
+            sell_orders: Vec::new(),
+            market_orders: Vec::new(),
             order_book: None,
             best_ask_price: None,
             best_bid_price: None,
+            book_stats: None,
+            book_health: BookHealth::Normal,
+            locked_since: None,
+            last_resync: None,
+            place_backoff_until: None,
+            trade_tape: TradeTape::new(TRADE_TAPE_HORIZON),
+            queue: QueueTracker::new(symbols),
+            counters: OrderCounters::default(),
+            fees: Fees::default(),
+            pnl: Pnl::new(),
+            started_at: get_timestamp()?,
         })
     }
 
     pub async fn get_btc_balance(&mut self, client: &Client, config: &Config) -> Result<()> {
         if let Some(balance) = get_balance::balance(client).await? {
             if let (Some(btc), Some(btc_reserved)) = (balance.btc, balance.btc_reserved) {
-                self.btc_balance =
-                    ((btc + btc_reserved) * config.size_base).round() / config.size_base;
+                self.btc_balance = config.round_size(btc + btc_reserved);
             }
         }
         Ok(())
     }
 
+    // Fetch the maker and taker fees of our pair
+    pub async fn get_fees(&mut self, client: &Client) -> Result<()> {
+        if let Some(accounts) = get_accounts::accounts(client).await? {
+            match accounts.exchange_fees.get(&self.symbol) {
+                Some(rates) => {
+                    self.fees = Fees::from_percent(
+                        rates.maker_fee_rate.unwrap_or_default(),
+                        rates.taker_fee_rate.unwrap_or_default(),
+                    );
+                    info!("fees for {}: {:?}", self.symbol, self.fees);
+                }
+                None => warn!("no fees reported for {}", self.symbol),
+            }
+        }
+        Ok(())
+    }
+
+    // Count the fees actually charged on our fills of this session
+    pub async fn get_transactions(&mut self, client: &Client) -> Result<()> {
+        if let Some(transactions) = get_transactions::transactions(client).await? {
+            self.pnl
+                .on_transactions(&transactions, &self.symbol, self.started_at);
+        }
+        Ok(())
+    }
+
     pub async fn get_active_orders(&mut self, client: &Client) -> Result<()> {
         if let Some(orders) = get_active_orders::opens(client).await? {
             let (mut buy_orders, mut sell_orders) = (Vec::new(), Vec::new());
 
+            // Keep the placement time of orders we already know
+            let placed_at = |id: usize| {
+                self.orders()
+                    .find(|order| order.id == id)
+                    .and_then(|order| order.placed_at)
+            };
             orders
                 .into_iter()
+                .map(|order| Order {
+                    placed_at: placed_at(order.id),
+                    ..order
+                })
                 .for_each(|order| match order.side.as_ref() {
                     "buy" => buy_orders.push(order),
                     "sell" => sell_orders.push(order),
                     _ => (),
                 });
 
-            async fn get_order(client: &Client, orders: &mut Vec<Order>) -> Result<Option<Order>> {
-                match orders.len() {
-                    0 => Ok(None),
-                    1 => Ok(Some(orders[0].clone())),
-                    _ => {
-                        if let Some(order) = orders.pop() {
-                            // cancel all other orders
-                            for order in orders {
-                                cancel_order::cancel_order(client, order.id).await?;
-                            }
-                            Ok(Some(order))
-                        } else {
-                            Ok(None)
-                        }
-                    }
-                }
-            }
-
-            self.buy_order = get_order(&client, &mut buy_orders).await?;
-            self.sell_order = get_order(&client, &mut sell_orders).await?;
+            // The reconciler cancels any order the strategy no longer wants
+            self.buy_orders = buy_orders;
+            self.sell_orders = sell_orders;
+            // Market orders have executed by now and the balance was refreshed with them
+            self.market_orders.clear();
+            self.track_queue();
         }
 
         Ok(())
     }
 
-    pub fn handle_execution_events(
-        &mut self,
-        execution_response: ExecutionResponse,
-        config: &Config,
-    ) {
+    pub fn handle_execution_events(&mut self, execution_response: ExecutionResponse) -> Vec<Fill> {
         // Convert the execution response into a vector of Execution objects
         let executions: Vec<Execution> = execution_response
-            .into_iter()
-            .map(|exec| Execution {
-                size: exec[4].parse().unwrap(),
-                maker_id: exec[7].clone(),
+            .iter()
+            .filter_map(|exec| match Execution::parse(exec) {
+                Ok(execution) => Some(execution),
+                Err(e) => {
+                    warn!("skipping malformed execution {:?}: {:?}", exec, e);
+                    None
+                }
             })
             .collect();
 
-        executions.into_iter().for_each(|exec| {
-            if let Some(order) = &mut self.buy_order {
-                // Check if the maker ID of the execution matches the ID of the buy order
-                if order.id == exec.maker_id.parse::<usize>().unwrap() {
-                    if order.size == exec.size {
-                        // If the sizes match, remove the buy order from the state
-                        self.buy_order = None;
-                    } else {
-                        // If the sizes do not match, update the size of the buy order
-                        order.size = ((order.size - exec.size) * config.size_base).round()
-                            / config.size_base;
-                    }
-                    // Update the BTC balance in the state
-                    self.btc_balance = ((self.btc_balance + exec.size) * config.size_base).round()
-                        / config.size_base;
-                    debug!("self.btc_balance: {}", self.btc_balance);
+        let mut fills = Vec::new();
+        for exec in executions {
+            self.trade_tape.add(&exec);
+            self.queue.on_trade(&exec);
+            for orders in [
+                &mut self.buy_orders,
+                &mut self.sell_orders,
+                &mut self.market_orders,
+            ] {
+                if let Some(fill) = fill_order(orders, &exec) {
+                    fills.push(fill);
                 }
             }
-            if let Some(order) = &mut self.sell_order {
-                // Check if the maker ID of the execution matches the ID of the sell order
-                if order.id == exec.maker_id.parse::<usize>().unwrap() {
-                    if order.size == exec.size {
-                        // If the sizes match, remove the sell order from the state
-                        self.sell_order = None;
-                    } else {
-                        // If the sizes do not match, update the size of the sell order
-                        order.size = ((order.size - exec.size) * config.size_base).round()
-                            / config.size_base;
-                    }
-                    // Update the BTC balance in the state
-                    self.btc_balance = ((self.btc_balance - exec.size) * config.size_base).round()
-                        / config.size_base;
-                    debug!("self.btc_balance: {}", self.btc_balance);
-                }
+        }
+        for fill in &fills {
+            // Update the BTC balance in the state
+            match fill.side.as_str() {
+                "buy" => self.btc_balance += fill.size,
+                _ => self.btc_balance -= fill.size,
             }
-        });
+            debug!("self.btc_balance: {}", self.btc_balance);
+            self.counters.on_fill(fill.order_id);
+            self.pnl.on_fill(fill);
+        }
+        self.track_queue();
+        fills
     }
 
-    pub fn update_order_book(&mut self, delta_data: &OrderBook) {
-        if let Some(order_book_data) = &mut self.order_book {
-            // Iterate over the bids in the new data
-            for delta_bid in delta_data.bids.iter() {
-                // Flag to track if the bid was found in the existing order book
-                let mut found = false;
-                // Iterate over the bids in the existing order book
-                for bid in order_book_data.bids.iter_mut() {
-                    // Check if the bid price matches
-                    if bid[0] == delta_bid[0] {
-                        // Set the found flag to true
-                        found = true;
-                        // Update the bid size with the new value
-                        bid[1] = delta_bid[1];
-                        // Break out of the inner loop
-                        break;
-                    }
+    // Estimated queue position of one of our resting orders
+    pub fn queue_position(&self, order_id: usize) -> Option<&QueuePosition> {
+        self.queue.get(order_id)
+    }
+
+    // All our live orders, bids first
+    pub fn orders(&self) -> impl Iterator<Item = &Order> {
+        self.buy_orders.iter().chain(self.sell_orders.iter())
+    }
+
+    // Cancel every live order, e.g. before shutting down
+    pub async fn cancel_all_orders(&mut self, client: &Client) -> Result<()> {
+        let order_ids: Vec<usize> = self.orders().map(|order| order.id).collect();
+        for order_id in order_ids {
+            self.cancel(client, order_id).await?;
+        }
+        Ok(())
+    }
+
+    // Start tracking the queue position of new orders and forget orders that are gone
+    fn track_queue(&mut self) {
+        let live_order_ids: Vec<usize> = self.orders().map(|order| order.id).collect();
+        self.queue.retain(&live_order_ids);
+        if let Some(order_book) = &self.order_book {
+            for order in self.buy_orders.iter().chain(self.sell_orders.iter()) {
+                if self.queue.contains(order.id) {
+                    continue;
                 }
-                // If the bid was not found and its size is not zero, add it to the order book
-                if !found && delta_bid[1] != 0.0 {
-                    order_book_data.bids.push(*delta_bid);
+                // Orders we did not place in this session were found resting in the book
+                match order.placed_at {
+                    Some(_) => self.queue.on_acknowledged(order, order_book),
+                    None => self.queue.on_adopted(order, order_book),
                 }
             }
-            // Remove any bids with a size of zero from the order book
-            order_book_data.bids.retain(|&x| x[1] != 0.0);
-            // Sort the bids in descending order by price
-            order_book_data
-                .bids
-                .sort_by(|a, b| b[0].partial_cmp(&a[0]).unwrap());
-
-            // Iterate over the asks in the new data
-            for delta_ask in delta_data.asks.iter() {
-                // Flag to track if the ask was found in the existing order book
-                let mut found = false;
-                // Iterate over the asks in the existing order book
-                for ask in order_book_data.asks.iter_mut() {
-                    // Check if the ask price matches
-                    if ask[0] == delta_ask[0] {
-                        // Set the found flag to true
-                        found = true;
-                        // Update the ask size with the new value
-                        ask[1] = delta_ask[1];
-                        // Break out of the inner loop
-                        break;
+        }
+    }
+
+    // Book view without our own resting orders, to tell whether anyone else is at the top
+    pub fn competing_view(&self) -> Option<CompetingView<'_>> {
+        let own = |orders: &[Order]| orders.iter().map(|o| (o.price, o.size)).collect::<Vec<_>>();
+        self.order_book.as_ref().map(|order_book| {
+            CompetingView::new(order_book, own(&self.buy_orders), own(&self.sell_orders))
+        })
+    }
+
+    // Best bid price of other participants
+    pub fn best_competing_bid_price(&self) -> Option<Price> {
+        self.competing_view()?.best_bid().map(|(price, _)| price)
+    }
+
+    // Best ask price of other participants
+    pub fn best_competing_ask_price(&self) -> Option<Price> {
+        self.competing_view()?.best_ask().map(|(price, _)| price)
+    }
+
+    // Trade statistics of our pair over the `window` before `now` (milliseconds)
+    pub fn trade_stats(&self, window: Duration, now: u64) -> TradeStats {
+        self.trade_tape.stats(&self.symbol, window, now)
+    }
+
+    // Replace the order book with a snapshot
+    pub fn set_order_book(&mut self, snapshot: &OrderBookData) {
+        self.order_book = Some(OrderBook::from(snapshot));
+        self.update_best_prices();
+    }
+
+    // Replace the order book with a fresh REST snapshot
+    pub async fn resync_order_book(&mut self, client: &Client) -> Result<()> {
+        let snapshot = get_order_books::get_order_books(client, &self.symbol).await?;
+        self.set_order_book(&snapshot);
+        self.last_resync = Some(Instant::now());
+        // A lock the snapshot still shows is real, so it is timed again
+        if self.locked_since.is_some() {
+            self.locked_since = self.last_resync;
+        }
+        info!("order book resynced from snapshot");
+        Ok(())
+    }
+
+    // Resync the order book if it is crossed, or locked for longer than LOCKED_RESYNC_DELAY, at
+    // most once per BOOK_RESYNC_INTERVAL
+    pub async fn resync_if_unhealthy(&mut self, client: &Client) -> Result<()> {
+        let due = self
+            .last_resync
+            .is_none_or(|at| at.elapsed() >= BOOK_RESYNC_INTERVAL);
+        let stale_lock = self
+            .locked_since
+            .is_some_and(|since| since.elapsed() >= LOCKED_RESYNC_DELAY);
+        if (self.book_health == BookHealth::Crossed || stale_lock) && due {
+            warn!(
+                "order book {:?} (bid {:?}, ask {:?}), resyncing",
+                self.book_health, self.best_bid_price, self.best_ask_price
+            );
+            // A failed attempt also counts, so retries stay within the rate limit
+            self.last_resync = Some(Instant::now());
+            self.resync_order_book(client).await?;
+        }
+        Ok(())
+    }
+
+    // Compare the order book against a fresh REST snapshot and resync it if it keeps diverging
+    pub async fn check_order_book(
+        &mut self,
+        client: &Client,
+        check: &mut IntegrityCheck,
+    ) -> Result<()> {
+        let snapshot = get_order_books::get_order_books(client, &self.symbol).await?;
+        if let Some(order_book) = &self.order_book {
+            if let Verdict::Resync { .. } = check.check(order_book, &OrderBook::from(&snapshot)) {
+                self.set_order_book(&snapshot);
+                self.last_resync = Some(Instant::now());
+                info!("order book resynced from snapshot");
+            }
+        }
+        Ok(())
+    }
+
+    pub fn update_order_book(&mut self, delta_data: &OrderBookData) {
+        if let Some(order_book) = &mut self.order_book {
+            order_book.apply(delta_data);
+            self.queue.on_book_update(order_book);
+            self.update_best_prices();
+        }
+    }
+
+    fn update_best_prices(&mut self) {
+        if let Some(order_book) = &self.order_book {
+            self.best_bid_price = order_book.best_bid_price();
+            self.best_ask_price = order_book.best_ask_price();
+            self.book_stats = BookStats::compute(order_book, DEPTH_LEVELS);
+            let book_health = order_book.health();
+            if book_health != self.book_health {
+                info!("order book health: {:?}", book_health);
+                self.locked_since = (book_health == BookHealth::Locked).then(Instant::now);
+            }
+            self.book_health = book_health;
+        }
+    }
+
+    // Market and account state for the strategy, once the book has both sides
+    pub fn snapshot(&self) -> Option<Snapshot<'_>> {
+        let book_stats = self.book_stats.as_ref()?;
+        Some(Snapshot {
+            symbol: &self.symbol,
+            timestamp: get_timestamp().ok()?,
+            order_book: self.order_book.as_ref()?,
+            book_stats,
+            trade_tape: &self.trade_tape,
+            fees: self.fees,
+            // Fall back to the whole book when only our own orders are left on a side
+            best_bid: self
+                .best_competing_bid_price()
+                .unwrap_or(book_stats.best_bid.0),
+            best_ask: self
+                .best_competing_ask_price()
+                .unwrap_or(book_stats.best_ask.0),
+            btc_balance: self.btc_balance,
+            buy_orders: &self.buy_orders,
+            sell_orders: &self.sell_orders,
+        })
+    }
+
+    // Ask the strategy for its quotes and reconcile our orders with them. When an order was
+    // actually cancelled or placed, another pass may be needed to settle.
+    pub async fn execute_orders(
+        &mut self,
+        client: &Client,
+        strategy: &mut dyn Strategy,
+        reconciler: &Reconciler,
+    ) -> Result<QuotePass> {
+        let mut pass = QuotePass::default();
+        // Do not quote into a locked or crossed book
+        if self.book_health != BookHealth::Normal {
+            return Ok(pass);
+        }
+        let quotes = match self.snapshot() {
+            Some(snapshot) => strategy.quotes(&snapshot),
+            None => return Ok(pass),
+        };
+        let live_orders: Vec<Order> = self.orders().cloned().collect();
+
+        let now = Instant::now();
+        let actions = reconciler.reconcile(&quotes, &live_orders, now);
+        // Keep the current orders instead of resending a rejected one on every pass
+        let backing_off = self.place_backoff_until.is_some_and(|until| now < until);
+        for action in &actions {
+            debug!("{:?}", action);
+            if backing_off && !matches!(action, Action::Cancel { .. }) {
+                debug!("holding back {:?} after a rejected order", action);
+                pass.retry_at = self.place_backoff_until;
+                continue;
+            }
+            pass.first_sent_at.get_or_insert_with(Instant::now);
+            // A rejected order is retried once the backoff ends
+            match action {
+                Action::Cancel { order_id } => {
+                    pass.progressed |= self.cancel(client, *order_id).await?;
+                }
+                Action::Place(quote) => {
+                    if self.place(client, quote).await? {
+                        pass.progressed = true;
+                    } else {
+                        pass.retry_at = self.place_backoff_until;
                     }
                 }
-                // If the ask was not found and its size is not zero, add it to the order book
-                if !found && delta_ask[1] != 0.0 {
-                    order_book_data.asks.push(*delta_ask);
+                Action::Amend { order_id, quote } => {
+                    if self.cancel(client, *order_id).await? {
+                        pass.progressed = true;
+                        if !self.place(client, quote).await? {
+                            pass.retry_at = self.place_backoff_until;
+                        }
+                    }
                 }
             }
-            // Remove any asks with a size of zero from the order book
-            order_book_data.asks.retain(|&x| x[1] != 0.0);
-            // Sort the asks in ascending order by price
-            order_book_data
-                .asks
-                .sort_by(|a, b| a[0].partial_cmp(&b[0]).unwrap());
-
-            self.best_bid_price = Some(order_book_data.bids[0][0]);
-            self.best_ask_price = Some(order_book_data.asks[0][0]);
         }
+        self.track_queue();
+
+        Ok(pass)
     }
 
-    pub async fn execute_orders(&mut self, client: &Client, config: &Config) -> Result<()> {
-        if let (Some(best_bid_price), Some(best_ask_price)) =
-            (self.best_bid_price, self.best_ask_price)
-        {
-            if let Some(order) = &self.buy_order {
-                // If the best bid price has changed, cancel the existing buy order
-                if best_bid_price != order.price {
-                    let res = cancel_order::cancel_order(client, order.id).await?;
-                    if res.success {
-                        self.buy_order = None;
-                    }
+    // Work off a breach of the hard inventory limits. While rebalancing is active, normal quoting
+    // is suspended and this returns when to run again.
+    pub async fn rebalance(
+        &mut self,
+        client: &Client,
+        rebalancer: &mut Rebalancer,
+    ) -> Result<Option<Instant>> {
+        if !rebalancer.check(self.btc_balance) {
+            return Ok(None);
+        }
+        let now = Instant::now();
+        // Book updates trigger another pass once prices are known
+        let (Some(best_bid), Some(best_ask)) = (self.best_bid_price, self.best_ask_price) else {
+            return Ok(Some(now + rebalancer.rules.slice_interval));
+        };
+        let Some(slice) = rebalancer.next_slice(now, self.btc_balance, best_bid, best_ask) else {
+            return Ok(Some(rebalancer.next_slice_at(now)));
+        };
+
+        // Free the reserved balance and drop what is left of the previous slice
+        if self.orders().next().is_some() {
+            info!("cancelling {} orders to rebalance", self.orders().count());
+            self.cancel_all_orders(client).await?;
+        }
+        let order = place_order::place(
+            client,
+            &self.symbol,
+            slice.side.as_str(),
+            slice.size,
+            slice.order_type,
+        )
+        .await?;
+        match order {
+            Some(mut order) => {
+                info!("placed rebalancing order {:?}", order);
+                order.placed_at = Some(now);
+                self.counters.placed += 1;
+                match slice.order_type {
+                    place_order::OrderType::Market { .. } => self.market_orders.push(order),
+                    _ => self.orders_mut(slice.side).push(order),
                 }
-            } else if self.btc_balance < 0.005 {
-                // If there is no existing buy order and the BTC balance is below the minimum order size, place a new buy order
-                self.buy_order =
-                    place_order::order(client, &self.symbol, "buy", best_bid_price, config.size)
-                        .await?;
             }
+            None => warn!("rebalancing order rejected: {:?}", slice),
+        }
+        self.track_queue();
+        Ok(Some(rebalancer.next_slice_at(now)))
+    }
 
-            if let Some(order) = &self.sell_order {
-                // If the best ask price has changed, cancel the existing sell order
-                if best_ask_price != order.price {
-                    let res = cancel_order::cancel_order(client, order.id).await?;
-                    if res.success {
-                        self.sell_order = None;
-                    }
-                }
-            } else if self.btc_balance >= 0.005 {
-                // If there is no existing sell order and the BTC balance is above the minimum order size, place a new sell order
-                self.sell_order = place_order::order(
-                    client,
-                    &self.symbol,
-                    "sell",
-                    best_ask_price,
-                    self.btc_balance,
-                )
-                .await?;
+    fn orders_mut(&mut self, side: Side) -> &mut Vec<Order> {
+        match side {
+            Side::Buy => &mut self.buy_orders,
+            Side::Sell => &mut self.sell_orders,
+        }
+    }
+
+    // Cancel one of our orders and forget it once the exchange confirms
+    async fn cancel(&mut self, client: &Client, order_id: usize) -> Result<bool> {
+        let res = cancel_order::cancel_order(client, order_id).await?;
+        if res.success {
+            self.counters.cancelled += 1;
+            for orders in [&mut self.buy_orders, &mut self.sell_orders] {
+                orders.retain(|order| order.id != order_id);
+            }
+        }
+        Ok(res.success)
+    }
+
+    // Returns whether the order was accepted; a rejection holds back new orders for a while
+    async fn place(&mut self, client: &Client, quote: &Quote) -> Result<bool> {
+        let order = place_order::order(
+            client,
+            &self.symbol,
+            quote.side.as_str(),
+            quote.price,
+            quote.size,
+        )
+        .await?;
+        match order {
+            Some(mut order) => {
+                order.placed_at = Some(Instant::now());
+                self.counters.placed += 1;
+                self.orders_mut(quote.side).push(order);
+                Ok(true)
+            }
+            None => {
+                self.place_backoff_until = Some(Instant::now() + REJECTED_ORDER_BACKOFF);
+                Ok(false)
             }
         }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn execution(size: &str, taker_side: &str, taker_id: &str, maker_id: &str) -> [String; 8] {
+        [
+            "1663318663",
+            "2357062",
+            "btc_jpy",
+            "2820896.0",
+            size,
+            taker_side,
+            taker_id,
+            maker_id,
+        ]
+        .map(String::from)
+    }
+
+    #[test]
+    fn tracks_maker_and_taker_fills() -> Result<()> {
+        let mut state = State::new("btc_jpy")?;
+        state.buy_orders.push(Order {
+            id: 1,
+            side: "buy".to_string(),
+            price: "2820896".parse()?,
+            size: "0.02".parse()?,
+            placed_at: None,
+        });
+        state.sell_orders.push(Order {
+            id: 2,
+            side: "sell".to_string(),
+            price: "2820896".parse()?,
+            size: "0.02".parse()?,
+            placed_at: None,
+        });
+
+        let fills = state.handle_execution_events(vec![
+            execution("0.01", "sell", "99", "1"),
+            execution("0.02", "sell", "2", "98"),
+        ]);
+
+        assert_eq!(fills.len(), 2);
+        assert_eq!(fills[0].liquidity, Liquidity::Maker);
+        assert_eq!(fills[1].liquidity, Liquidity::Taker);
+        assert_eq!(state.buy_orders[0].size, "0.01".parse()?);
+        assert!(state.sell_orders.is_empty());
+        assert_eq!(state.btc_balance, "-0.01".parse()?);
+        // A second partial fill of the same order is not another filled order
+        state.handle_execution_events(vec![execution("0.005", "sell", "99", "1")]);
+        assert_eq!(state.counters.fills(), 2);
+        assert_eq!(state.btc_balance, "-0.005".parse()?);
+
+        // A market order is not a live order, but its fills still count
+        state.market_orders.push(Order {
+            id: 3,
+            side: "sell".to_string(),
+            price: "2820896".parse()?,
+            size: "0.01".parse()?,
+            placed_at: None,
+        });
+        assert_eq!(state.orders().count(), 1);
+        let fills = state.handle_execution_events(vec![execution("0.01", "sell", "3", "97")]);
+        assert_eq!(fills.len(), 1);
+        assert!(state.market_orders.is_empty());
+        assert_eq!(state.btc_balance, "-0.015".parse()?);
+        Ok(())
+    }
 
+    #[test]
+    fn times_how_long_the_book_is_locked() -> Result<()> {
+        let mut state = State::new("btc_jpy")?;
+        let book = |bid: &str, ask: &str| -> OrderBookData {
+            serde_json::from_value(serde_json::json!({
+                "bids": [[bid, "1"]],
+                "asks": [[ask, "1"]],
+            }))
+            .unwrap()
+        };
+        state.set_order_book(&book("100", "101"));
+        assert_eq!(state.locked_since, None);
+        state.set_order_book(&book("100", "100"));
+        assert_eq!(state.book_health, BookHealth::Locked);
+        let locked_since = state.locked_since;
+        assert!(locked_since.is_some());
+        // Further updates of a locked book keep the time it locked
+        state.update_order_book(&book("100", "100"));
+        assert_eq!(state.locked_since, locked_since);
+        state.update_order_book(&serde_json::from_value(serde_json::json!({
+            "bids": [["100", "0"]],
+            "asks": [],
+        }))?);
+        assert_eq!(state.locked_since, None);
         Ok(())
     }
 }
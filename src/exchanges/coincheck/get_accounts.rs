@@ -0,0 +1,52 @@
+use crate::opt_string_or_float;
+use crate::util::{get_keys, get_timestamp, sign};
+use anyhow::Result;
+use log::debug;
+use reqwest::Client;
+use serde::Deserialize;
+use std::collections::HashMap;
+
+// Fee rates of a pair in percent of the notional
+#[derive(Debug, Deserialize, Clone)]
+pub struct FeeRates {
+    #[serde(with = "opt_string_or_float")]
+    pub maker_fee_rate: Option<f64>,
+    #[serde(with = "opt_string_or_float")]
+    pub taker_fee_rate: Option<f64>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct Response {
+    pub success: bool,
+    #[serde(default)]
+    pub exchange_fees: HashMap<String, FeeRates>,
+}
+
+pub async fn accounts(client: &Client) -> Result<Option<Response>> {
+    let (api_key, secret_key) = get_keys()?;
+    let timestamp = get_timestamp()?;
+    let endpoint = "https://coincheck.com";
+    let path = "/api/accounts";
+
+    let text = format!("{}{}{}", timestamp, endpoint, path);
+    let sign = sign(&text, &secret_key)?;
+
+    let res: Response = client
+        .get(&(endpoint.to_string() + path))
+        .header("content-type", "application/json")
+        .header("ACCESS-KEY", api_key)
+        .header("ACCESS-NONCE", timestamp)
+        .header("ACCESS-SIGNATURE", sign)
+        .send()
+        .await?
+        .json()
+        .await?;
+
+    debug!("{:?}", res);
+
+    if res.success {
+        Ok(Some(res))
+    } else {
+        Ok(None)
+    }
+}
@@ -1,3 +1,4 @@
+use crate::decimal::Size;
 use crate::opt_string_or_float;
 use crate::util::{get_keys, get_timestamp, sign};
 use anyhow::Result;
@@ -10,10 +11,8 @@ pub struct Response {
     pub success: bool,
     #[serde(with = "opt_string_or_float")]
     pub jpy: Option<f64>,
-    #[serde(with = "opt_string_or_float")]
-    pub btc: Option<f64>,
-    #[serde(with = "opt_string_or_float")]
-    pub btc_reserved: Option<f64>,
+    pub btc: Option<Size>,
+    pub btc_reserved: Option<Size>,
 }
 
 pub async fn balance(client: &Client) -> Result<Option<Response>> {
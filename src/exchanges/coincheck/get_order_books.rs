@@ -1,10 +1,11 @@
-use super::OrderBook;
+use super::OrderBookData;
 use anyhow::Result;
 use reqwest::Client;
 
-pub async fn get_order_books(client: &Client) -> Result<OrderBook> {
-    let res: OrderBook = client
+pub async fn get_order_books(client: &Client, pair: &str) -> Result<OrderBookData> {
+    let res: OrderBookData = client
         .get("https://coincheck.com/api/order_books")
+        .query(&[("pair", pair)])
         .send()
         .await?
         .json()
@@ -0,0 +1,46 @@
+use crate::decimal::{Price, Size};
+use anyhow::Result;
+use reqwest::Client;
+use serde::Deserialize;
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct Trade {
+    pub id: usize,
+    #[serde(alias = "rate")]
+    pub price: Price,
+    #[serde(alias = "amount")]
+    pub size: Size,
+    pub pair: String,
+    // Side of the taker
+    pub order_type: String,
+    pub created_at: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct Response {
+    success: bool,
+    data: Vec<Trade>,
+}
+
+// Fetch the most recent public trades of a pair, oldest first
+pub async fn get_trades(client: &Client, pair: &str, limit: usize) -> Result<Vec<Trade>> {
+    let res: Response = client
+        .get("https://coincheck.com/api/trades")
+        .query(&[
+            ("pair", pair),
+            ("limit", &limit.to_string()),
+            ("order", "desc"),
+        ])
+        .send()
+        .await?
+        .json()
+        .await?;
+
+    if res.success {
+        let mut trades = res.data;
+        trades.sort_by_key(|trade| trade.id);
+        Ok(trades)
+    } else {
+        Ok(Vec::new())
+    }
+}
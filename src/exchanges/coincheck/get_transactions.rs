@@ -0,0 +1,67 @@
+use crate::decimal::{Price, Size};
+use crate::opt_string_or_float;
+use crate::util::{get_keys, get_timestamp, sign};
+use anyhow::Result;
+use log::debug;
+use reqwest::Client;
+use serde::Deserialize;
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct Funds {
+    pub btc: Size,
+    #[serde(with = "opt_string_or_float")]
+    pub jpy: Option<f64>,
+}
+
+// One of our recent fills with the fee actually charged
+#[derive(Debug, Deserialize, Clone)]
+pub struct Transaction {
+    pub id: usize,
+    pub order_id: usize,
+    pub created_at: String,
+    pub funds: Funds,
+    pub pair: String,
+    #[serde(alias = "rate")]
+    pub price: Price,
+    pub fee_currency: Option<String>,
+    #[serde(with = "opt_string_or_float")]
+    pub fee: Option<f64>,
+    // "M" for maker, "T" for taker
+    pub liquidity: String,
+    pub side: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct Response {
+    success: bool,
+    transactions: Option<Vec<Transaction>>,
+}
+
+pub async fn transactions(client: &Client) -> Result<Option<Vec<Transaction>>> {
+    let (api_key, secret_key) = get_keys()?;
+    let timestamp = get_timestamp()?;
+    let endpoint = "https://coincheck.com";
+    let path = "/api/exchange/orders/transactions";
+
+    let text = format!("{}{}{}", timestamp, endpoint, path);
+    let sign = sign(&text, &secret_key)?;
+
+    let res: Response = client
+        .get(&(endpoint.to_string() + path))
+        .header("content-type", "application/json")
+        .header("ACCESS-KEY", api_key)
+        .header("ACCESS-NONCE", timestamp)
+        .header("ACCESS-SIGNATURE", sign)
+        .send()
+        .await?
+        .json()
+        .await?;
+
+    debug!("{:?}", res);
+
+    if res.success {
+        Ok(res.transactions)
+    } else {
+        Ok(None)
+    }
+}
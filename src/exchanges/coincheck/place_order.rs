@@ -1,4 +1,5 @@
 use super::Order;
+use crate::decimal::{Price, Size};
 use crate::util::{get_keys, get_timestamp, sign};
 use anyhow::Result;
 use log::debug;
@@ -12,21 +13,57 @@ struct Response {
     pub id: Option<usize>,
     pub rate: Option<String>,
     pub amount: Option<String>,
-    pub order_type: Option<String>,
 }
 
+// How an order is executed
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OrderType {
+    // Limit order that is rejected rather than taking liquidity
+    PostOnly { price: Price },
+    // Limit order that may cross the spread
+    Limit { price: Price },
+    // Market order. Market buys are sized in JPY, converted at the expected price.
+    Market { expected_price: Price },
+}
+
+// Place a post-only limit order
 pub async fn order(
     client: &Client,
     symbol: &str,
     side: &str,
-    price: f64,
-    size: f64,
+    price: Price,
+    size: Size,
+) -> Result<Option<Order>> {
+    place(client, symbol, side, size, OrderType::PostOnly { price }).await
+}
+
+pub async fn place(
+    client: &Client,
+    symbol: &str,
+    side: &str,
+    size: Size,
+    order_type: OrderType,
 ) -> Result<Option<Order>> {
     let (api_key, secret_key) = get_keys()?;
     let timestamp = get_timestamp()?;
     let endpoint = "https://coincheck.com";
     let path = "/api/exchange/orders";
-    let parameters = json!({ "pair": symbol, "order_type": side,  "rate": price, "amount": size, "time_in_force": "post_only" });
+    let parameters = match order_type {
+        OrderType::PostOnly { price } => {
+            json!({ "pair": symbol, "order_type": side, "rate": price, "amount": size, "time_in_force": "post_only" })
+        }
+        OrderType::Limit { price } => {
+            json!({ "pair": symbol, "order_type": side, "rate": price, "amount": size })
+        }
+        OrderType::Market { expected_price } if side == "buy" => {
+            // Whole yen, rounded up so the fill covers the requested size at the expected price
+            let jpy = expected_price.notional_ceil(size, Price::ONE)?;
+            json!({ "pair": symbol, "order_type": "market_buy", "market_buy_amount": jpy })
+        }
+        OrderType::Market { .. } => {
+            json!({ "pair": symbol, "order_type": "market_sell", "amount": size })
+        }
+    };
 
     let text = format!("{}{}{}{}", timestamp, endpoint, path, &parameters);
     let sign = sign(&text, &secret_key)?;
@@ -45,21 +82,27 @@ pub async fn order(
 
     debug!("{:?}", res);
 
-    if res.success {
-        if let (Some(id), Some(rate), Some(amount), Some(order_type)) =
-            (res.id, res.rate, res.amount, res.order_type)
-        {
-            let order = Order {
-                id,
-                side: order_type,
-                price: rate.parse()?,
-                size: amount.parse()?,
-            };
-            Ok(Some(order))
-        } else {
-            Ok(None)
-        }
-    } else {
-        Ok(None)
+    if !res.success {
+        return Ok(None);
     }
+    let Some(id) = res.id else {
+        return Ok(None);
+    };
+    // Market orders come back without a rate, and market buys without an amount
+    let price = match (res.rate, order_type) {
+        (Some(rate), _) => rate.parse()?,
+        (None, OrderType::Market { expected_price }) => expected_price,
+        (None, _) => return Ok(None),
+    };
+    let size = match res.amount {
+        Some(amount) => amount.parse()?,
+        None => size,
+    };
+    Ok(Some(Order {
+        id,
+        side: side.to_string(),
+        price,
+        size,
+        placed_at: None,
+    }))
 }
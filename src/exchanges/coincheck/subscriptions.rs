@@ -0,0 +1,181 @@
+use serde_json::json;
+use std::collections::{BTreeSet, VecDeque};
+use tokio::sync::mpsc;
+use tokio_tungstenite::tungstenite::protocol::Message;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Command {
+    Subscribe(String),
+    Unsubscribe(String),
+}
+
+impl Command {
+    pub fn channel(&self) -> &str {
+        match self {
+            Command::Subscribe(channel) | Command::Unsubscribe(channel) => channel,
+        }
+    }
+
+    // WebSocket message sent to Coincheck for this command
+    pub fn message(&self) -> Message {
+        let (kind, channel) = match self {
+            Command::Subscribe(channel) => ("subscribe", channel),
+            Command::Unsubscribe(channel) => ("unsubscribe", channel),
+        };
+        Message::Text(json!({ "type": kind, "channel": channel }).to_string())
+    }
+}
+
+// Handle for other tasks to change subscriptions at runtime
+#[derive(Clone)]
+pub struct SubscriptionHandle {
+    sender: mpsc::UnboundedSender<Command>,
+}
+
+impl SubscriptionHandle {
+    pub fn subscribe(&self, channel: &str) {
+        let _ = self.sender.send(Command::Subscribe(channel.to_string()));
+    }
+
+    pub fn unsubscribe(&self, channel: &str) {
+        let _ = self.sender.send(Command::Unsubscribe(channel.to_string()));
+    }
+}
+
+// Tracks the active channels and queues the messages that still have to be sent.
+// Coincheck drops subscriptions sent in quick succession, so the owner of the WebSocket
+// sends at most one queued message per second.
+pub struct Subscriptions {
+    channels: BTreeSet<String>,
+    pending: VecDeque<Command>,
+    receiver: mpsc::UnboundedReceiver<Command>,
+    sender: mpsc::UnboundedSender<Command>,
+}
+
+impl Subscriptions {
+    pub fn new() -> Subscriptions {
+        let (sender, receiver) = mpsc::unbounded_channel();
+        Subscriptions {
+            channels: BTreeSet::new(),
+            pending: VecDeque::new(),
+            receiver,
+            sender,
+        }
+    }
+
+    pub fn handle(&self) -> SubscriptionHandle {
+        SubscriptionHandle {
+            sender: self.sender.clone(),
+        }
+    }
+
+    pub fn subscribe(&mut self, channel: &str) {
+        self.apply(Command::Subscribe(channel.to_string()));
+    }
+
+    pub fn unsubscribe(&mut self, channel: &str) {
+        self.apply(Command::Unsubscribe(channel.to_string()));
+    }
+
+    pub fn apply(&mut self, command: Command) {
+        let changed = match &command {
+            Command::Subscribe(channel) => self.channels.insert(channel.clone()),
+            Command::Unsubscribe(channel) => self.channels.remove(channel),
+        };
+        if changed {
+            // A newer command for the same channel supersedes one that was not sent yet
+            self.pending.retain(|c| c.channel() != command.channel());
+            self.pending.push_back(command);
+        }
+    }
+
+    // Wait for a command sent through a handle
+    pub async fn recv(&mut self) -> Option<Command> {
+        self.receiver.recv().await
+    }
+
+    pub fn channels(&self) -> impl Iterator<Item = &str> {
+        self.channels.iter().map(String::as_str)
+    }
+
+    pub fn is_subscribed(&self, channel: &str) -> bool {
+        self.channels.contains(channel)
+    }
+
+    pub fn has_pending(&self) -> bool {
+        !self.pending.is_empty()
+    }
+
+    // Take the next command to send over the WebSocket
+    pub fn next_pending(&mut self) -> Option<Command> {
+        self.pending.pop_front()
+    }
+
+    // Queue every active channel again after reconnecting
+    pub fn resubscribe_all(&mut self) {
+        self.pending = self
+            .channels
+            .iter()
+            .map(|channel| Command::Subscribe(channel.clone()))
+            .collect();
+    }
+}
+
+impl Default for Subscriptions {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tracks_channels_and_replays_after_reconnect() {
+        let mut subscriptions = Subscriptions::new();
+        subscriptions.subscribe("btc_jpy-orderbook");
+        subscriptions.subscribe("btc_jpy-trades");
+        subscriptions.subscribe("eth_jpy-trades");
+        subscriptions.unsubscribe("eth_jpy-trades");
+
+        assert_eq!(
+            subscriptions.next_pending(),
+            Some(Command::Subscribe("btc_jpy-orderbook".to_string()))
+        );
+        assert_eq!(
+            subscriptions.next_pending(),
+            Some(Command::Subscribe("btc_jpy-trades".to_string()))
+        );
+        assert_eq!(
+            subscriptions.next_pending(),
+            Some(Command::Unsubscribe("eth_jpy-trades".to_string()))
+        );
+        assert!(!subscriptions.has_pending());
+
+        subscriptions.resubscribe_all();
+        let replayed: Vec<Command> = std::iter::from_fn(|| subscriptions.next_pending()).collect();
+        assert_eq!(
+            replayed,
+            vec![
+                Command::Subscribe("btc_jpy-orderbook".to_string()),
+                Command::Subscribe("btc_jpy-trades".to_string()),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn applies_commands_sent_through_a_handle() {
+        let mut subscriptions = Subscriptions::new();
+        let handle = subscriptions.handle();
+        handle.subscribe("eth_jpy-orderbook");
+
+        let command = subscriptions.recv().await.unwrap();
+        subscriptions.apply(command);
+        assert!(subscriptions.is_subscribed("eth_jpy-orderbook"));
+        assert_eq!(
+            subscriptions.next_pending(),
+            Some(Command::Subscribe("eth_jpy-orderbook".to_string()))
+        );
+    }
+}
@@ -0,0 +1,21 @@
+// Fee rates of a pair as fractions of the notional; negative rates are rebates
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Fees {
+    pub maker: f64,
+    pub taker: f64,
+}
+
+impl Fees {
+    // Convert from the percentages reported by the exchange
+    pub fn from_percent(maker: f64, taker: f64) -> Fees {
+        Fees {
+            maker: maker / 100.0,
+            taker: taker / 100.0,
+        }
+    }
+
+    // Fees of buying and selling back as a maker, in basis points of the notional
+    pub fn round_trip_bps(&self) -> f64 {
+        2.0 * self.maker * 10_000.0
+    }
+}
@@ -0,0 +1,138 @@
+use crate::{decimal::Price, order_book::OrderBook};
+use log::{debug, warn};
+use std::collections::BTreeSet;
+
+// Outcome of comparing the local book against a REST snapshot
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Verdict {
+    // The books agree within the tolerance
+    Consistent,
+    // The books differ, but not for long enough to resync yet
+    Diverged { mismatched_levels: usize },
+    // The books have differed for too many consecutive checks and the local book must be replaced
+    Resync { mismatched_levels: usize },
+}
+
+// Periodic comparison of the incrementally maintained book against REST snapshots.
+// The snapshot is never taken at exactly the same moment as the last delta, so a few differing
+// levels are tolerated and only repeated divergence triggers a resync.
+pub struct IntegrityCheck {
+    // Number of top levels compared per side
+    pub levels: usize,
+    // Number of mismatched levels tolerated in a single check
+    pub tolerance: usize,
+    // Number of consecutive divergent checks before resyncing
+    pub threshold: u32,
+    pub consecutive_divergences: u32,
+    pub checks: u64,
+    pub divergences: u64,
+    pub resyncs: u64,
+}
+
+impl IntegrityCheck {
+    pub fn new(levels: usize, tolerance: usize, threshold: u32) -> IntegrityCheck {
+        IntegrityCheck {
+            levels,
+            tolerance,
+            threshold,
+            consecutive_divergences: 0,
+            checks: 0,
+            divergences: 0,
+            resyncs: 0,
+        }
+    }
+
+    pub fn check(&mut self, local: &OrderBook, snapshot: &OrderBook) -> Verdict {
+        self.checks += 1;
+        let mismatched_levels = mismatched_levels(local, snapshot, self.levels);
+
+        if mismatched_levels <= self.tolerance {
+            debug!(
+                "order book consistent ({} mismatched levels)",
+                mismatched_levels
+            );
+            self.consecutive_divergences = 0;
+            return Verdict::Consistent;
+        }
+
+        self.divergences += 1;
+        self.consecutive_divergences += 1;
+        warn!(
+            "order book diverged from snapshot: {} mismatched levels ({} in a row, {} of {} checks)",
+            mismatched_levels, self.consecutive_divergences, self.divergences, self.checks
+        );
+
+        if self.consecutive_divergences >= self.threshold {
+            self.consecutive_divergences = 0;
+            self.resyncs += 1;
+            Verdict::Resync { mismatched_levels }
+        } else {
+            Verdict::Diverged { mismatched_levels }
+        }
+    }
+}
+
+// Count the prices among the top levels of either book whose size differs between the two books
+pub fn mismatched_levels(local: &OrderBook, snapshot: &OrderBook, levels: usize) -> usize {
+    let bid_prices: BTreeSet<Price> = local
+        .bids()
+        .take(levels)
+        .chain(snapshot.bids().take(levels))
+        .map(|(price, _)| price)
+        .collect();
+    let ask_prices: BTreeSet<Price> = local
+        .asks()
+        .take(levels)
+        .chain(snapshot.asks().take(levels))
+        .map(|(price, _)| price)
+        .collect();
+
+    let bids = bid_prices
+        .into_iter()
+        .filter(|&price| local.bid_size_at(price) != snapshot.bid_size_at(price))
+        .count();
+    let asks = ask_prices
+        .into_iter()
+        .filter(|&price| local.ask_size_at(price) != snapshot.ask_size_at(price))
+        .count();
+    bids + asks
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::exchanges::coincheck::OrderBookData;
+    use serde_json::json;
+
+    fn order_book(value: serde_json::Value) -> OrderBook {
+        OrderBook::from(&serde_json::from_value::<OrderBookData>(value).unwrap())
+    }
+
+    #[test]
+    fn resyncs_after_repeated_divergence() {
+        let local = order_book(json!({
+            "bids": [["100", "1"], ["99", "1"]],
+            "asks": [["101", "1"], ["102", "1"]],
+        }));
+        let snapshot = order_book(json!({
+            "bids": [["100", "2"], ["98", "1"]],
+            "asks": [["101", "1"], ["102", "1"]],
+        }));
+        let mut check = IntegrityCheck::new(10, 1, 2);
+
+        assert_eq!(check.check(&local, &local), Verdict::Consistent);
+        assert_eq!(
+            check.check(&local, &snapshot),
+            Verdict::Diverged {
+                mismatched_levels: 3
+            }
+        );
+        assert_eq!(
+            check.check(&local, &snapshot),
+            Verdict::Resync {
+                mismatched_levels: 3
+            }
+        );
+        assert_eq!((check.checks, check.divergences, check.resyncs), (3, 2, 1));
+    }
+}
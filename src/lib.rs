@@ -1,6 +1,22 @@
+pub mod analytics;
+pub mod bus;
+pub mod candle;
 pub mod config;
+pub mod decimal;
 pub mod exchanges;
+pub mod fees;
+pub mod integrity;
 pub mod opt_string_or_float;
-pub mod string_or_float;
+pub mod order_book;
+pub mod placement;
+pub mod pnl;
+pub mod queue_position;
+pub mod quote_trigger;
+pub mod rebalancer;
+pub mod reconciler;
+pub mod recorder;
+pub mod replay;
+pub mod strategy;
+pub mod trade_tape;
 pub mod util;
-pub mod vec_string_or_float;
+pub mod volatility;
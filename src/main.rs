@@ -1,137 +1,297 @@
 use anyhow::Result;
 use coincheck_rust::{
+    bus::{Bus, ConnectionState, MarketEvent},
+    candle::{self, CandleAggregator},
     config::Config,
-    exchanges::coincheck::{self, CoincheckWebsocketEvent},
+    decimal::Price,
+    exchanges::coincheck::{self, subscriptions::Command, CoincheckWebsocketEvent},
+    integrity::IntegrityCheck,
+    quote_trigger::QuoteTrigger,
+    rebalancer::Rebalancer,
+    reconciler::Reconciler,
+    recorder, strategy,
+    util::get_timestamp,
 };
 use futures_util::{SinkExt, StreamExt};
-use log::debug;
+use log::{debug, error, info, warn};
 use reqwest::Client;
-use serde_json::json;
-use std::{process, time::Duration};
-use tokio::signal::unix::SignalKind;
+use std::{
+    env,
+    time::{Duration, Instant},
+};
+use tokio::{signal::unix::SignalKind, time::MissedTickBehavior};
 use tokio_tungstenite::{connect_async, tungstenite::protocol::Message};
 
 static SYMBOL: &str = "btc_jpy";
+// Delay before reconnecting to the WebSocket API
+const RECONNECT_DELAY: Duration = Duration::from_secs(5);
+// Minimum time between two quoting decisions
+const MIN_REQUOTE_INTERVAL: Duration = Duration::from_millis(50);
 
 #[tokio::main]
 async fn main() -> Result<()> {
     env_logger::init();
 
-    let config = Config::new(SYMBOL, 0.02, 1.0, 0.00000001)?;
+    let mut config = Config::new(SYMBOL, 0.02, 1.0, 0.00000001)?;
+    if let Ok(name) = env::var("STRATEGY") {
+        config.strategy = name.parse()?;
+    }
+    let mut strategy = strategy::from_config(&config)?;
+    info!("quoting with the {} strategy", strategy.name());
+    let reconciler = Reconciler::new(&config);
+    let mut rebalancer = Rebalancer::new(&config, coincheck::MIN_ORDER_SIZE);
     let mut state = coincheck::State::new(SYMBOL)?;
     let client = Client::new();
+    state.get_fees(&client).await?;
+    // Market data bus for consumers other than the trading loop (recorders, metrics, strategies)
+    let bus = Bus::new(1024);
 
-    // Connect to the Coincheck WebSocket API
-    let (coincheck_stream, _) = connect_async("wss://ws-api.coincheck.com").await?;
-    let (mut coincheck_write, mut coincheck_read) = coincheck_stream.split();
+    // Record the raw feed and periodic snapshots when a recording directory is configured
+    let recorder_task = env::var("RECORD_DIR").ok().map(|dir| {
+        let subscriber = bus.subscribe("recorder");
+        let client = client.clone();
+        tokio::spawn(async move {
+            let pairs = vec![SYMBOL.to_string()];
+            if let Err(e) =
+                recorder::run(subscriber, client, &dir, pairs, Duration::from_secs(60)).await
+            {
+                error!("recorder stopped: {:?}", e);
+            }
+        })
+    });
 
-    // Subscribe to the order book for the specified symbol
-    coincheck_write
-        .send(Message::Text(
-            json!({
-                "type": "subscribe",
-                "channel": "btc_jpy-orderbook",
-            })
-            .to_string(),
-        ))
-        .await?;
-    // Wait for 1 second
-    tokio::time::sleep(Duration::from_secs(1)).await;
+    // Build one-minute candles from the trades and publish them on the bus
+    {
+        let subscriber = bus.subscribe("candles");
+        let bus = bus.clone();
+        let aggregator = CandleAggregator::new(SYMBOL, Duration::from_secs(60));
+        let client = client.clone();
+        tokio::spawn(async move {
+            if let Err(e) = candle::run(subscriber, bus, aggregator, client).await {
+                error!("candle aggregator stopped: {:?}", e);
+            }
+        });
+    }
 
-    // Subscribe to the trades for the specified symbol
-    coincheck_write
-        .send(Message::Text(
-            json!({
-                "type": "subscribe",
-                "channel": "btc_jpy-trades",
-            })
-            .to_string(),
-        ))
-        .await?;
+    // Subscribe to the order book and the trades for the specified symbol
+    let mut subscriptions = coincheck::subscriptions::Subscriptions::new();
+    subscriptions.subscribe(&format!("{}-orderbook", SYMBOL));
+    subscriptions.subscribe(&format!("{}-trades", SYMBOL));
+    // Strategies may follow other pairs, whose data is routed to them
+    strategy.on_start(subscriptions.handle());
 
     // Set the interval for getting the state to 20 seconds. This is for recovering from occasional misbehavior.
     let mut get_state_interval = tokio::time::interval(Duration::from_secs(20));
-    // Set the interval for executing orders to 200 milliseconds
-    let mut execute_orders_interval = tokio::time::interval(Duration::from_millis(200));
+    // Re-quote when the book or our orders change instead of polling
+    let mut quote_trigger = QuoteTrigger::new(MIN_REQUOTE_INTERVAL);
+    // Compare the order book against a REST snapshot every 30 seconds
+    let mut check_order_book_interval = tokio::time::interval(Duration::from_secs(30));
+    // Tolerate one differing level among the top 10 per side, and resync after 3 divergent checks in a row
+    let mut integrity_check = IntegrityCheck::new(10, 1, 3);
+    // Send at most one subscription message per second
+    let mut subscription_interval = tokio::time::interval(Duration::from_secs(1));
+    subscription_interval.set_missed_tick_behavior(MissedTickBehavior::Delay);
     // Set up signal handlers for SIGTERM and SIGINT
     let mut sigterm = tokio::signal::unix::signal(SignalKind::terminate())?;
     let mut sigint = tokio::signal::unix::signal(SignalKind::interrupt())?;
 
-    let orderbook = coincheck::get_order_books::get_order_books(&client).await?;
-    state.order_book = Some(orderbook);
-
     loop {
-        // Use the `select!` macro to wait for multiple events
-        tokio::select! {
-            // Wait for a message from the Coincheck WebSocket API
-            msg = coincheck_read.next() => {
-                if let Some(msg) = msg {
+        // Connect to the Coincheck WebSocket API
+        let coincheck_stream = match connect_async("wss://ws-api.coincheck.com").await {
+            Ok((coincheck_stream, _)) => coincheck_stream,
+            Err(e) => {
+                warn!("failed to connect: {:?}", e);
+                tokio::time::sleep(RECONNECT_DELAY).await;
+                continue;
+            }
+        };
+        let (mut coincheck_write, mut coincheck_read) = coincheck_stream.split();
+        bus.publish(MarketEvent::Connection(ConnectionState::Connected));
+        // Replay the active subscriptions on the new connection
+        subscriptions.resubscribe_all();
+
+        let shutdown = 'session: loop {
+            let quote_deadline = quote_trigger.deadline();
+            // Use the `select!` macro to wait for multiple events
+            tokio::select! {
+                // Wait for a message from the Coincheck WebSocket API
+                msg = coincheck_read.next() => {
+                    let msg = match msg {
+                        Some(Ok(msg)) => msg,
+                        // If the connection fails or ends, reconnect
+                        Some(Err(e)) => {
+                            warn!("connection error: {:?}", e);
+                            break false;
+                        }
+                        None => break false,
+                    };
+                    let received_at = Instant::now();
                     debug!("msg: {:?}", msg);
-                    match msg? {
+                    match msg {
                         // If the message is a ping, send a pong response
                         Message::Ping(ping) => {
-                            coincheck_write.send(Message::Pong(ping)).await?;
+                            if let Err(e) = coincheck_write.send(Message::Pong(ping)).await {
+                                warn!("failed to send pong: {:?}", e);
+                                break false;
+                            }
                         }
                         // If the message is text, parse it as JSON
                         Message::Text(text) =>  {
-                            match serde_json::from_str(&text)? {
+                            bus.publish(MarketEvent::Message {
+                                received_at: get_timestamp()?,
+                                text: text.clone(),
+                            });
+                            // Channels subscribed by the strategy may carry other messages
+                            let event = match serde_json::from_str(&text) {
+                                Ok(event) => event,
+                                Err(e) => {
+                                    warn!("skipping unrecognized message {}: {:?}", text, e);
+                                    continue 'session;
+                                }
+                            };
+                            match event {
                                 // If the message is an order book update, update the state
                                 CoincheckWebsocketEvent::OrderBookUpdateEvent(order_book_response) => {
                                     let order_book_event = order_book_response.1;
-                                    state.update_order_book(&order_book_event);
+                                    let is_ours = order_book_response.0 == state.symbol;
+                                    if is_ours {
+                                        state.update_order_book(&order_book_event);
+                                        // A later update retries once BOOK_RESYNC_INTERVAL has passed
+                                        if let Err(e) = state.resync_if_unhealthy(&client).await {
+                                            warn!("order book resync failed: {:?}", e);
+                                        }
+                                        quote_trigger.notify(received_at);
+                                    }
+                                    let event = MarketEvent::OrderBook {
+                                        pair: order_book_response.0,
+                                        order_book: order_book_event,
+                                    };
+                                    // Pairs subscribed by the strategy are routed to it
+                                    if !is_ours {
+                                        strategy.on_market_event(&event);
+                                    }
+                                    bus.publish(event);
                                 }
                                 // If the message is an execution event, handle it
                                 CoincheckWebsocketEvent::ExecutionEvent(execution_events) => {
-                                    state.handle_execution_events(execution_events, &config);
+                                    bus.publish(MarketEvent::Trades(execution_events.clone()));
+                                    let (execution_events, others): (Vec<_>, Vec<_>) = execution_events
+                                        .into_iter()
+                                        .partition(|execution| execution[2] == state.symbol);
+                                    if !others.is_empty() {
+                                        strategy.on_market_event(&MarketEvent::Trades(others));
+                                    }
+                                    let fills = state.handle_execution_events(execution_events);
+                                    for fill in &fills {
+                                        strategy.on_fill(fill);
+                                    }
+                                    if !fills.is_empty() {
+                                        quote_trigger.notify(received_at);
+                                    }
                                 }
                             }
                         }
+                        // If the server closes the connection, reconnect
+                        Message::Close(_) => break false,
                         _ => ()
                     }
                 }
-            }
-            // Wait for the get state interval to tick
-            _ = get_state_interval.tick() => {
-                state.get_btc_balance(&client, &config).await?;
-                state.get_active_orders(&client).await?;
-            }
-            // Wait for the execute orders interval to tick
-            _ = execute_orders_interval.tick() => {
-                state.execute_orders(&client, &config).await?;
-            }
-            // Wait for a SIGTERM signal
-            _ = sigterm.recv() => {
-                if let Some(order) = state.buy_order {
-                    coincheck::cancel_order::cancel_order(&client, order.id).await?;
-                }
-                if let Some(order) = state.sell_order {
-                    coincheck::cancel_order::cancel_order(&client, order.id).await?;
+                // Send the next queued subscription message
+                _ = subscription_interval.tick(), if subscriptions.has_pending() => {
+                    if let Some(command) = subscriptions.next_pending() {
+                        // The command is replayed with the other subscriptions after reconnecting
+                        if let Err(e) = coincheck_write.send(command.message()).await {
+                            warn!("failed to send {:?}: {:?}", command, e);
+                            break false;
+                        }
+                        // Seed the order book once its updates are flowing
+                        if command == Command::Subscribe(format!("{}-orderbook", SYMBOL)) {
+                            let orderbook = coincheck::get_order_books::get_order_books(&client, SYMBOL).await?;
+                            state.set_order_book(&orderbook);
+                            quote_trigger.follow_up(Instant::now());
+                        }
+                    }
                 }
-                coincheck_write.close().await?;
-                process::exit(0);
-            },
-            // Wait for a SIGINT signal
-            _ = sigint.recv() => {
-                if let Some(order) = state.buy_order {
-                    coincheck::cancel_order::cancel_order(&client, order.id).await?;
+                // Apply subscription changes requested at runtime
+                Some(command) = subscriptions.recv() => {
+                    subscriptions.apply(command);
                 }
-                if let Some(order) = state.sell_order {
-                    coincheck::cancel_order::cancel_order(&client, order.id).await?;
+                // Wait for the get state interval to tick
+                _ = get_state_interval.tick() => {
+                    state.get_btc_balance(&client, &config).await?;
+                    state.get_active_orders(&client).await?;
+                    quote_trigger.follow_up(Instant::now());
+                    quote_trigger.log_latency();
+                    info!(
+                        "orders: {} placed, {} cancelled, {} filled, {} cancels per fill",
+                        state.counters.placed,
+                        state.counters.cancelled,
+                        state.counters.fills(),
+                        state.counters.cancels_per_fill().map_or("-".to_string(), |c| format!("{:.1}", c))
+                    );
+                    // Fees are picked up on the next refresh if this one fails
+                    if let Err(e) = state.get_transactions(&client).await {
+                        warn!("failed to fetch transactions: {:?}", e);
+                    }
+                    if let Some(mid) = state.book_stats.as_ref().map(|stats| Price::from_f64(stats.mid)) {
+                        info!(
+                            "pnl: {:.0} net, {:.0} gross, {:.0} fees, position {}",
+                            state.pnl.net(mid),
+                            state.pnl.gross(mid),
+                            state.pnl.fees,
+                            state.pnl.position
+                        );
+                    }
                 }
-                coincheck_write.close().await?;
-                process::exit(0);
-            },
-            // Wait for a Ctrl-C signal from the user
-            _ = tokio::signal::ctrl_c() => {
-                if let Some(order) = state.buy_order {
-                    coincheck::cancel_order::cancel_order(&client, order.id).await?;
+                // Wait for the order book check interval to tick
+                _ = check_order_book_interval.tick() => {
+                    // A failed check is skipped; the next one runs on schedule
+                    if let Err(e) = state.check_order_book(&client, &mut integrity_check).await {
+                        warn!("order book check failed: {:?}", e);
+                    }
                 }
-                if let Some(order) = state.sell_order {
-                    coincheck::cancel_order::cancel_order(&client, order.id).await?;
+                // Quote once the pending decision is due
+                _ = tokio::time::sleep_until(quote_deadline.unwrap_or_else(Instant::now).into()), if quote_deadline.is_some() => {
+                    let received_at = quote_trigger.fire(Instant::now());
+                    // Quoting is suspended while a breach of the hard inventory limits is worked off
+                    match state.rebalance(&client, &mut rebalancer).await? {
+                        Some(next_slice_at) => quote_trigger.follow_up(next_slice_at),
+                        None => {
+                            let pass = state.execute_orders(&client, strategy.as_mut(), &reconciler).await?;
+                            if let (Some(received_at), Some(sent_at)) = (received_at, pass.first_sent_at) {
+                                quote_trigger.on_sent(received_at, sent_at);
+                            }
+                            // A cancelled order is replaced on a follow-up pass
+                            if pass.progressed {
+                                quote_trigger.follow_up(Instant::now());
+                            }
+                            // Orders held back after a rejection are sent once the backoff ends
+                            if let Some(retry_at) = pass.retry_at {
+                                quote_trigger.follow_up(retry_at);
+                            }
+                        }
+                    }
                 }
-                coincheck_write.close().await?;
-                process::exit(0);
+                // Wait for a SIGTERM signal
+                _ = sigterm.recv() => break 'session true,
+                // Wait for a SIGINT signal
+                _ = sigint.recv() => break 'session true,
+                // Wait for a Ctrl-C signal from the user
+                _ = tokio::signal::ctrl_c() => break 'session true,
+            }
+        };
+
+        if shutdown {
+            state.cancel_all_orders(&client).await?;
+            coincheck_write.close().await?;
+            // Let the recorder finish its files before exiting
+            bus.publish(MarketEvent::Shutdown);
+            if let Some(recorder_task) = recorder_task {
+                recorder_task.await?;
             }
+            return Ok(());
         }
+        bus.publish(MarketEvent::Connection(ConnectionState::Disconnected));
+        tokio::time::sleep(RECONNECT_DELAY).await;
     }
 }
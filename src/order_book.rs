@@ -0,0 +1,242 @@
+use crate::{
+    decimal::{Price, Size},
+    exchanges::coincheck::OrderBookData,
+};
+use std::collections::BTreeMap;
+
+// Relation between the best bid and the best ask
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum BookHealth {
+    // Best bid below best ask, or a side is empty
+    #[default]
+    Normal,
+    // Best bid equal to best ask
+    Locked,
+    // Best bid above best ask, which means the book missed an update
+    Crossed,
+}
+
+// Order book keyed by price, with O(log n) level updates
+#[derive(Debug, Clone, Default)]
+pub struct OrderBook {
+    bids: BTreeMap<Price, Size>,
+    asks: BTreeMap<Price, Size>,
+}
+
+impl OrderBook {
+    pub fn new() -> OrderBook {
+        OrderBook::default()
+    }
+
+    // Apply a delta. A level with a size of zero is removed from the book.
+    pub fn apply(&mut self, delta: &OrderBookData) {
+        for &(price, size) in delta.bids.iter() {
+            apply_level(&mut self.bids, price, size);
+        }
+        for &(price, size) in delta.asks.iter() {
+            apply_level(&mut self.asks, price, size);
+        }
+    }
+
+    pub fn best_bid(&self) -> Option<(Price, Size)> {
+        self.bids().next()
+    }
+
+    pub fn best_ask(&self) -> Option<(Price, Size)> {
+        self.asks().next()
+    }
+
+    pub fn best_bid_price(&self) -> Option<Price> {
+        self.best_bid().map(|(price, _)| price)
+    }
+
+    pub fn best_ask_price(&self) -> Option<Price> {
+        self.best_ask().map(|(price, _)| price)
+    }
+
+    // Bid levels as (price, size), best (highest) price first
+    pub fn bids(&self) -> impl Iterator<Item = (Price, Size)> + '_ {
+        self.bids.iter().rev().map(|(price, size)| (*price, *size))
+    }
+
+    // Ask levels as (price, size), best (lowest) price first
+    pub fn asks(&self) -> impl Iterator<Item = (Price, Size)> + '_ {
+        self.asks.iter().map(|(price, size)| (*price, *size))
+    }
+
+    pub fn bid_size_at(&self, price: Price) -> Option<Size> {
+        self.bids.get(&price).copied()
+    }
+
+    pub fn ask_size_at(&self, price: Price) -> Option<Size> {
+        self.asks.get(&price).copied()
+    }
+
+    pub fn health(&self) -> BookHealth {
+        match (self.best_bid_price(), self.best_ask_price()) {
+            (Some(bid), Some(ask)) if bid > ask => BookHealth::Crossed,
+            (Some(bid), Some(ask)) if bid == ask => BookHealth::Locked,
+            _ => BookHealth::Normal,
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.bids.is_empty() && self.asks.is_empty()
+    }
+}
+
+// View of the book with our own resting orders subtracted from their levels, so the best
+// prices are those of other participants
+pub struct CompetingView<'a> {
+    order_book: &'a OrderBook,
+    own_bids: BTreeMap<Price, Size>,
+    own_asks: BTreeMap<Price, Size>,
+}
+
+impl<'a> CompetingView<'a> {
+    // Build a view from our resting bids and asks as (price, size)
+    pub fn new(
+        order_book: &'a OrderBook,
+        own_bids: impl IntoIterator<Item = (Price, Size)>,
+        own_asks: impl IntoIterator<Item = (Price, Size)>,
+    ) -> CompetingView<'a> {
+        fn collect(orders: impl IntoIterator<Item = (Price, Size)>) -> BTreeMap<Price, Size> {
+            let mut levels = BTreeMap::new();
+            for (price, size) in orders {
+                *levels.entry(price).or_insert(Size::ZERO) += size;
+            }
+            levels
+        }
+        CompetingView {
+            order_book,
+            own_bids: collect(own_bids),
+            own_asks: collect(own_asks),
+        }
+    }
+
+    // Bid levels without our size, best price first. Levels holding only our orders are skipped.
+    pub fn bids(&self) -> impl Iterator<Item = (Price, Size)> + '_ {
+        self.order_book
+            .bids()
+            .filter_map(|level| competing(level, &self.own_bids))
+    }
+
+    // Ask levels without our size, best price first. Levels holding only our orders are skipped.
+    pub fn asks(&self) -> impl Iterator<Item = (Price, Size)> + '_ {
+        self.order_book
+            .asks()
+            .filter_map(|level| competing(level, &self.own_asks))
+    }
+
+    pub fn best_bid(&self) -> Option<(Price, Size)> {
+        self.bids().next()
+    }
+
+    pub fn best_ask(&self) -> Option<(Price, Size)> {
+        self.asks().next()
+    }
+}
+
+fn competing((price, size): (Price, Size), own: &BTreeMap<Price, Size>) -> Option<(Price, Size)> {
+    let remaining = size - own.get(&price).copied().unwrap_or_default();
+    if remaining > Size::ZERO {
+        Some((price, remaining))
+    } else {
+        None
+    }
+}
+
+impl From<&OrderBookData> for OrderBook {
+    fn from(snapshot: &OrderBookData) -> OrderBook {
+        let mut order_book = OrderBook::new();
+        order_book.apply(snapshot);
+        order_book
+    }
+}
+
+fn apply_level(side: &mut BTreeMap<Price, Size>, price: Price, size: Size) {
+    if size.is_zero() {
+        side.remove(&price);
+    } else {
+        side.insert(price, size);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn data(value: serde_json::Value) -> OrderBookData {
+        serde_json::from_value(value).unwrap()
+    }
+
+    fn level(price: &str, size: &str) -> (Price, Size) {
+        (price.parse().unwrap(), size.parse().unwrap())
+    }
+
+    #[test]
+    fn applies_deltas_in_price_order() {
+        let mut order_book = OrderBook::from(&data(json!({
+            "bids": [["100", "1"], ["99", "2"]],
+            "asks": [["101", "1"], ["102", "2"]],
+        })));
+
+        order_book.apply(&data(json!({
+            "bids": [["100.5", "3"], ["100", "0"]],
+            "asks": [["101", "0.5"]],
+        })));
+
+        assert_eq!(
+            order_book.bids().collect::<Vec<_>>(),
+            vec![level("100.5", "3"), level("99", "2")]
+        );
+        assert_eq!(order_book.best_ask(), Some(level("101", "0.5")));
+        assert_eq!(order_book.bid_size_at(level("100", "0").0), None);
+    }
+
+    #[test]
+    fn competing_view_skips_levels_holding_only_our_orders() {
+        let order_book = OrderBook::from(&data(json!({
+            "bids": [["100", "0.5"], ["99", "2"]],
+            "asks": [["101", "1"]],
+        })));
+
+        let view = CompetingView::new(
+            &order_book,
+            vec![level("100", "0.5")],
+            vec![level("101", "0.25")],
+        );
+
+        assert_eq!(view.best_bid(), Some(level("99", "2")));
+        assert_eq!(view.best_ask(), Some(level("101", "0.75")));
+    }
+
+    #[test]
+    fn detects_locked_and_crossed_books() {
+        let mut order_book = OrderBook::from(&data(json!({
+            "bids": [["100", "1"]],
+            "asks": [["101", "1"]],
+        })));
+        assert_eq!(order_book.health(), BookHealth::Normal);
+
+        order_book.apply(&data(json!({ "bids": [["101", "1"]], "asks": [] })));
+        assert_eq!(order_book.health(), BookHealth::Locked);
+
+        order_book.apply(&data(json!({ "bids": [["102", "1"]], "asks": [] })));
+        assert_eq!(order_book.health(), BookHealth::Crossed);
+    }
+
+    #[test]
+    fn emptied_side_has_no_best_price() {
+        let mut order_book = OrderBook::from(&data(json!({
+            "bids": [["100", "1"]],
+            "asks": [["101", "1"]],
+        })));
+
+        order_book.apply(&data(json!({ "bids": [["100", "0"]], "asks": [] })));
+
+        assert_eq!(order_book.best_bid_price(), None);
+        assert_eq!(order_book.best_ask_price(), Some(level("101", "1").0));
+    }
+}
@@ -0,0 +1,208 @@
+use crate::{
+    decimal::{Price, Size},
+    fees::Fees,
+    strategy::{Quote, Side},
+};
+
+// Where to place quotes relative to the best bid and ask
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Placement {
+    // Quote at the best prices
+    #[default]
+    Join,
+    // Step inside the spread by up to N ticks per side, as far as the spread allows
+    Improve(i64),
+    // Quote N ticks behind the best prices
+    BackOff(i64),
+}
+
+// Rules turning the top of the book into quote prices
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct QuoteRules {
+    pub placement: Placement,
+    // Never quote tighter than this many ticks, nor tighter than this many basis points of the mid
+    pub min_spread_ticks: i64,
+    pub min_spread_bps: f64,
+    // Edge required on top of the round-trip fees, in basis points of the mid
+    pub min_edge_bps: f64,
+}
+
+impl QuoteRules {
+    // Minimum distance between our bid and ask in ticks, at least one. A round trip between the
+    // two must cover the maker fees plus the required edge.
+    pub fn min_spread(&self, mid: f64, price_increment: Price, fees: &Fees) -> i64 {
+        let bps = self
+            .min_spread_bps
+            .max(fees.round_trip_bps() + self.min_edge_bps);
+        let bps_ticks = (mid * bps / 10_000.0 / price_increment.to_f64()).ceil();
+        self.min_spread_ticks.max(bps_ticks as i64).max(1)
+    }
+
+    // Bid and ask prices given the best prices of other participants
+    pub fn prices(
+        &self,
+        best_bid: Price,
+        best_ask: Price,
+        price_increment: Price,
+        fees: &Fees,
+    ) -> (Price, Price) {
+        let tick = price_increment;
+        let spread_ticks = (best_ask - best_bid).ticks(tick);
+
+        let (mut bid, mut ask) = match self.placement {
+            Placement::Join => (best_bid, best_ask),
+            Placement::Improve(ticks) => {
+                // Leave at least one tick between our bid and ask
+                let ticks = ticks.min((spread_ticks - 1) / 2).max(0);
+                (best_bid + tick.times(ticks), best_ask - tick.times(ticks))
+            }
+            Placement::BackOff(ticks) => {
+                (best_bid - tick.times(ticks), best_ask + tick.times(ticks))
+            }
+        };
+
+        // Widen around the current quotes until the minimum spread is met
+        let mid = (best_bid.to_f64() + best_ask.to_f64()) / 2.0;
+        let deficit = self.min_spread(mid, tick, fees) - (ask - bid).ticks(tick);
+        if deficit > 0 {
+            bid -= tick.times(deficit / 2);
+            ask += tick.times(deficit - deficit / 2);
+        }
+        (bid, ask)
+    }
+}
+
+// Several orders per side, `spacing_ticks` apart and stepping away from the spread. Each level is
+// sized as a multiple of the base size, best level first.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Ladder {
+    pub spacing_ticks: i64,
+    pub sizes: Vec<f64>,
+}
+
+impl Default for Ladder {
+    fn default() -> Ladder {
+        Ladder {
+            spacing_ticks: 1,
+            sizes: vec![1.0],
+        }
+    }
+}
+
+impl Ladder {
+    pub fn levels(&self) -> usize {
+        self.sizes.len()
+    }
+
+    // Quotes for one side starting at `price`, with at most `max_total` on the whole side.
+    // Sizes are not rounded to the size increment.
+    pub fn quotes(
+        &self,
+        side: Side,
+        price: Price,
+        size: Size,
+        max_total: Size,
+        price_increment: Price,
+    ) -> Vec<Quote> {
+        let step = match side {
+            Side::Buy => -price_increment.times(self.spacing_ticks),
+            Side::Sell => price_increment.times(self.spacing_ticks),
+        };
+        let mut remaining = max_total;
+        let mut quotes = Vec::new();
+        for (level, weight) in self.sizes.iter().enumerate() {
+            let size = Size::from_f64(size.to_f64() * weight).min(remaining);
+            if size <= Size::ZERO {
+                break;
+            }
+            remaining -= size;
+            quotes.push(Quote {
+                side,
+                price: price + step.times(level as i64),
+                size,
+            });
+        }
+        quotes
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn prices(rules: QuoteRules, bid: &str, ask: &str) -> (String, String) {
+        let (bid, ask) = rules.prices(
+            bid.parse().unwrap(),
+            ask.parse().unwrap(),
+            Price::from_f64(1.0),
+            &Fees::default(),
+        );
+        (bid.to_string(), ask.to_string())
+    }
+
+    fn pair(bid: &str, ask: &str) -> (String, String) {
+        (bid.to_string(), ask.to_string())
+    }
+
+    #[test]
+    fn applies_offsets_and_minimum_spread() {
+        let mut rules = QuoteRules::default();
+        assert_eq!(prices(rules, "100", "110"), pair("100", "110"));
+
+        rules.placement = Placement::Improve(2);
+        assert_eq!(prices(rules, "100", "110"), pair("102", "108"));
+        // Only as far as the spread allows
+        assert_eq!(prices(rules, "100", "103"), pair("101", "102"));
+        assert_eq!(prices(rules, "100", "101"), pair("100", "101"));
+
+        rules.placement = Placement::BackOff(1);
+        assert_eq!(prices(rules, "100", "101"), pair("99", "102"));
+
+        // 100 bps of a 1000 mid is 10 ticks
+        rules = QuoteRules {
+            placement: Placement::Join,
+            min_spread_ticks: 4,
+            min_spread_bps: 100.0,
+            min_edge_bps: 0.0,
+        };
+        assert_eq!(prices(rules, "999", "1001"), pair("995", "1005"));
+
+        // Maker fees of 0.1% each way plus 20 bps of edge need 40 bps, 4 ticks of a 1000 mid
+        rules = QuoteRules {
+            min_edge_bps: 20.0,
+            ..QuoteRules::default()
+        };
+        let fees = Fees::from_percent(0.1, 0.15);
+        let (bid, ask) = rules.prices(
+            "999".parse().unwrap(),
+            "1001".parse().unwrap(),
+            Price::from_f64(1.0),
+            &fees,
+        );
+        assert_eq!((bid.to_string(), ask.to_string()), pair("998", "1002"));
+    }
+
+    #[test]
+    fn ladders_away_from_the_spread() {
+        let ladder = Ladder {
+            spacing_ticks: 2,
+            sizes: vec![1.0, 2.0, 3.0],
+        };
+        let quotes = ladder.quotes(
+            Side::Buy,
+            "100".parse().unwrap(),
+            "0.01".parse().unwrap(),
+            "0.04".parse().unwrap(),
+            Price::from_f64(1.0),
+        );
+        let levels: Vec<(String, String)> = quotes
+            .iter()
+            .map(|quote| (quote.price.to_string(), quote.size.to_string()))
+            .collect();
+        // The last level is capped by the total
+        assert_eq!(
+            levels,
+            vec![pair("100", "0.01"), pair("98", "0.02"), pair("96", "0.01")]
+        );
+    }
+}
@@ -0,0 +1,118 @@
+use crate::{
+    decimal::{Price, Size},
+    exchanges::coincheck::{get_transactions::Transaction, Fill},
+    util::timestamp_from_iso8601,
+};
+use log::debug;
+use std::collections::HashSet;
+
+// Profit and loss of our fills since startup, in quote currency. Fills come from the trades
+// channel as they happen, while the fees actually charged arrive later from the transactions
+// endpoint.
+#[derive(Debug, Default)]
+pub struct Pnl {
+    pub position: Size,
+    // Cash received from sells minus cash paid for buys
+    pub cash: f64,
+    pub fees: f64,
+    pub volume: Size,
+    // Transactions whose fee has been counted
+    seen_transactions: HashSet<usize>,
+}
+
+impl Pnl {
+    pub fn new() -> Pnl {
+        Pnl::default()
+    }
+
+    pub fn on_fill(&mut self, fill: &Fill) {
+        let notional = fill.price.to_f64() * fill.size.to_f64();
+        match fill.side.as_str() {
+            "buy" => {
+                self.position += fill.size;
+                self.cash -= notional;
+            }
+            "sell" => {
+                self.position -= fill.size;
+                self.cash += notional;
+            }
+            _ => return,
+        }
+        self.volume += fill.size;
+    }
+
+    // Count the fees of transactions not seen before. Only transactions created at or after
+    // `since` (milliseconds) belong to this session.
+    pub fn on_transactions(&mut self, transactions: &[Transaction], pair: &str, since: u64) {
+        for transaction in transactions {
+            if transaction.pair != pair || self.seen_transactions.contains(&transaction.id) {
+                continue;
+            }
+            match timestamp_from_iso8601(&transaction.created_at) {
+                Ok(created_at) if created_at >= since => (),
+                _ => continue,
+            }
+            self.seen_transactions.insert(transaction.id);
+            // Fees charged in the base currency are converted at the fill price
+            let fee = transaction.fee.unwrap_or_default();
+            self.fees += match transaction.fee_currency.as_deref() {
+                Some("BTC") | Some("btc") => fee * transaction.price.to_f64(),
+                _ => fee,
+            };
+            debug!("fee of transaction {}: {}", transaction.id, fee);
+        }
+    }
+
+    // Cash plus the position marked at `mark`, before fees
+    pub fn gross(&self, mark: Price) -> f64 {
+        self.cash + self.position.to_f64() * mark.to_f64()
+    }
+
+    pub fn net(&self, mark: Price) -> f64 {
+        self.gross(mark) - self.fees
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::exchanges::coincheck::{get_transactions::Funds, Liquidity};
+
+    fn fill(side: &str, price: &str) -> Fill {
+        Fill {
+            order_id: 1,
+            side: side.to_string(),
+            price: price.parse().unwrap(),
+            size: "0.1".parse().unwrap(),
+            liquidity: Liquidity::Maker,
+        }
+    }
+
+    #[test]
+    fn includes_fees_once() {
+        let mut pnl = Pnl::new();
+        pnl.on_fill(&fill("buy", "1000"));
+        pnl.on_fill(&fill("sell", "1010"));
+        let transaction = Transaction {
+            id: 7,
+            order_id: 1,
+            created_at: "2024-01-01T00:00:00.000Z".to_string(),
+            funds: Funds {
+                btc: "-0.1".parse().unwrap(),
+                jpy: Some(101.0),
+            },
+            pair: "btc_jpy".to_string(),
+            price: "1010".parse().unwrap(),
+            fee_currency: Some("JPY".to_string()),
+            fee: Some(0.25),
+            liquidity: "M".to_string(),
+            side: "sell".to_string(),
+        };
+        pnl.on_transactions(&[transaction.clone(), transaction], "btc_jpy", 0);
+
+        let mark = "1005".parse().unwrap();
+        assert!((pnl.gross(mark) - 1.0).abs() < 1e-9);
+        assert!((pnl.net(mark) - 0.75).abs() < 1e-9);
+        assert_eq!(pnl.position, Size::ZERO);
+    }
+}
@@ -0,0 +1,259 @@
+use crate::{
+    analytics::BookSide,
+    decimal::{Price, Size},
+    exchanges::coincheck::{Execution, Order},
+    order_book::OrderBook,
+};
+use log::debug;
+use std::collections::HashMap;
+
+// Estimated place of one of our resting orders in the queue at its price level
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct QueuePosition {
+    pub order_id: usize,
+    pub side: BookSide,
+    pub price: Price,
+    pub size: Size,
+    // Size resting ahead of our order at the same price
+    pub ahead: Size,
+    // Reduction of the size ahead that the book showed before any trade reported it. A trade
+    // message arriving after its book update uses this up instead of counting twice.
+    seen_in_book: Size,
+}
+
+// Tracks queue positions of our resting orders on one pair. The size ahead starts at the size of
+// the level when the order is acknowledged, since our order joins at the back, and shrinks with
+// trades at our price. It never exceeds the size of the level without our order, so cancels
+// ahead of us are counted as well.
+#[derive(Debug)]
+pub struct QueueTracker {
+    pair: String,
+    positions: HashMap<usize, QueuePosition>,
+}
+
+impl QueueTracker {
+    pub fn new(pair: &str) -> QueueTracker {
+        QueueTracker {
+            pair: pair.to_string(),
+            positions: HashMap::new(),
+        }
+    }
+
+    // Start tracking an order that was just acknowledged, before the book shows it
+    pub fn on_acknowledged(&mut self, order: &Order, order_book: &OrderBook) {
+        self.track(order, order_book, Size::ZERO);
+    }
+
+    // Start tracking an order found resting in the book, whose size the level already includes
+    pub fn on_adopted(&mut self, order: &Order, order_book: &OrderBook) {
+        self.track(order, order_book, order.size);
+    }
+
+    fn track(&mut self, order: &Order, order_book: &OrderBook, own_size_in_level: Size) {
+        let side = match order.side.as_str() {
+            "buy" => BookSide::Bid,
+            "sell" => BookSide::Ask,
+            _ => return,
+        };
+        let level_size = match side {
+            BookSide::Bid => order_book.bid_size_at(order.price),
+            BookSide::Ask => order_book.ask_size_at(order.price),
+        }
+        .unwrap_or_default();
+        let ahead = (level_size - own_size_in_level).max(Size::ZERO);
+
+        debug!("order {} joined the queue behind {}", order.id, ahead);
+        self.positions.insert(
+            order.id,
+            QueuePosition {
+                order_id: order.id,
+                side,
+                price: order.price,
+                size: order.size,
+                ahead,
+                seen_in_book: Size::ZERO,
+            },
+        );
+    }
+
+    // Trades at our price against our side consume the queue ahead of us
+    pub fn on_trade(&mut self, execution: &Execution) {
+        if execution.pair != self.pair {
+            return;
+        }
+        // The maker of a trade rests on the side opposite to the taker
+        let maker_side = match execution.taker_side.as_str() {
+            "buy" => BookSide::Ask,
+            "sell" => BookSide::Bid,
+            _ => return,
+        };
+        for position in self.positions.values_mut() {
+            if position.side != maker_side || position.price != execution.price {
+                continue;
+            }
+            if position.order_id == execution.maker_id {
+                // Our order trading means nothing is left ahead of it
+                position.ahead = Size::ZERO;
+                position.seen_in_book = Size::ZERO;
+                position.size = (position.size - execution.size).max(Size::ZERO);
+            } else {
+                let seen = position.seen_in_book.min(execution.size);
+                position.seen_in_book -= seen;
+                position.ahead = (position.ahead - (execution.size - seen)).max(Size::ZERO);
+            }
+        }
+    }
+
+    // A level shrinking below our size plus the size ahead means orders ahead of us are gone
+    pub fn on_book_update(&mut self, order_book: &OrderBook) {
+        for position in self.positions.values_mut() {
+            let level_size = match position.side {
+                BookSide::Bid => order_book.bid_size_at(position.price),
+                BookSide::Ask => order_book.ask_size_at(position.price),
+            }
+            .unwrap_or_default();
+            let others = (level_size - position.size).max(Size::ZERO);
+            if others < position.ahead {
+                position.seen_in_book += position.ahead - others;
+                position.ahead = others;
+            }
+        }
+    }
+
+    pub fn remove(&mut self, order_id: usize) {
+        self.positions.remove(&order_id);
+    }
+
+    // Stop tracking orders that are no longer live
+    pub fn retain(&mut self, live_order_ids: &[usize]) {
+        self.positions
+            .retain(|order_id, _| live_order_ids.contains(order_id));
+    }
+
+    pub fn get(&self, order_id: usize) -> Option<&QueuePosition> {
+        self.positions.get(&order_id)
+    }
+
+    pub fn contains(&self, order_id: usize) -> bool {
+        self.positions.contains_key(&order_id)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::exchanges::coincheck::OrderBookData;
+    use serde_json::json;
+
+    fn size(value: &str) -> Size {
+        value.parse().unwrap()
+    }
+
+    #[test]
+    fn queue_shrinks_with_trades_and_cancels() {
+        let data: OrderBookData = serde_json::from_value(json!({
+            "bids": [["100", "3"]],
+            "asks": [["101", "1"]],
+        }))
+        .unwrap();
+        let mut order_book = OrderBook::from(&data);
+        let order = Order {
+            id: 7,
+            side: "buy".to_string(),
+            price: "100".parse().unwrap(),
+            size: size("1"),
+            placed_at: None,
+        };
+        let mut tracker = QueueTracker::new("btc_jpy");
+        tracker.on_acknowledged(&order, &order_book);
+        assert_eq!(tracker.get(7).map(|p| p.ahead), Some(size("3")));
+
+        // Someone sells 1 into the bid at our price
+        tracker.on_trade(&sell(&order, "btc_jpy", "1"));
+        assert_eq!(tracker.get(7).map(|p| p.ahead), Some(size("2")));
+        // Trades on other pairs do not move our queue
+        tracker.on_trade(&sell(&order, "eth_jpy", "1"));
+        assert_eq!(tracker.get(7).map(|p| p.ahead), Some(size("2")));
+
+        // The level now holds our 1 plus only 0.5 from others
+        order_book.apply(
+            &serde_json::from_value(json!({ "bids": [["100", "1.5"]], "asks": [] })).unwrap(),
+        );
+        tracker.on_book_update(&order_book);
+        assert_eq!(tracker.get(7).map(|p| p.ahead), Some(size("0.5")));
+    }
+
+    fn sell(order: &Order, pair: &str, amount: &str) -> Execution {
+        Execution {
+            timestamp: 0,
+            id: 1,
+            pair: pair.to_string(),
+            price: order.price,
+            size: size(amount),
+            taker_side: "sell".to_string(),
+            taker_id: 2,
+            maker_id: 3,
+        }
+    }
+
+    #[test]
+    fn counts_a_trade_once_when_the_book_shows_it_first() {
+        let data: OrderBookData = serde_json::from_value(json!({
+            "bids": [["100", "3"]],
+            "asks": [["101", "1"]],
+        }))
+        .unwrap();
+        let mut order_book = OrderBook::from(&data);
+        // Found resting with 2 ahead of it
+        let order = Order {
+            id: 7,
+            side: "buy".to_string(),
+            price: "100".parse().unwrap(),
+            size: size("1"),
+            placed_at: None,
+        };
+        let mut tracker = QueueTracker::new("btc_jpy");
+        tracker.on_adopted(&order, &order_book);
+        assert_eq!(tracker.get(7).map(|p| p.ahead), Some(size("2")));
+
+        // The delta for a trade of 1 arrives before the trade itself
+        order_book
+            .apply(&serde_json::from_value(json!({ "bids": [["100", "2"]], "asks": [] })).unwrap());
+        tracker.on_book_update(&order_book);
+        assert_eq!(tracker.get(7).map(|p| p.ahead), Some(size("1")));
+        tracker.on_trade(&sell(&order, "btc_jpy", "1"));
+        assert_eq!(tracker.get(7).map(|p| p.ahead), Some(size("1")));
+    }
+
+    #[test]
+    fn keeps_cancels_ahead_when_size_joins_behind() {
+        let data: OrderBookData = serde_json::from_value(json!({
+            "bids": [["100", "4"]],
+            "asks": [["101", "1"]],
+        }))
+        .unwrap();
+        let mut order_book = OrderBook::from(&data);
+        let order = Order {
+            id: 7,
+            side: "buy".to_string(),
+            price: "100".parse().unwrap(),
+            size: size("1"),
+            placed_at: None,
+        };
+        let mut tracker = QueueTracker::new("btc_jpy");
+        tracker.on_adopted(&order, &order_book);
+        assert_eq!(tracker.get(7).map(|p| p.ahead), Some(size("3")));
+
+        // Orders ahead of us cancel
+        order_book
+            .apply(&serde_json::from_value(json!({ "bids": [["100", "2"]], "asks": [] })).unwrap());
+        tracker.on_book_update(&order_book);
+        assert_eq!(tracker.get(7).map(|p| p.ahead), Some(size("1")));
+
+        // New orders join behind us
+        order_book
+            .apply(&serde_json::from_value(json!({ "bids": [["100", "7"]], "asks": [] })).unwrap());
+        tracker.on_book_update(&order_book);
+        assert_eq!(tracker.get(7).map(|p| p.ahead), Some(size("1")));
+    }
+}
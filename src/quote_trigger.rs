@@ -0,0 +1,149 @@
+use log::{debug, info};
+use std::time::{Duration, Instant};
+
+// Decides when to re-run the quoting logic. Book and fill events mark a quote as pending; a burst
+// of events before the next decision is coalesced into one, and decisions are spaced at least
+// `min_interval` apart so a busy book does not flood the exchange with cancels.
+pub struct QuoteTrigger {
+    pub min_interval: Duration,
+    // When the pending decision was first requested
+    pending_since: Option<Instant>,
+    // Receipt time of the oldest market event not yet acted on
+    event_received_at: Option<Instant>,
+    last_quote: Option<Instant>,
+    pub latency: LatencyStats,
+}
+
+// Time from receiving a market event to sending the first order it led to
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct LatencyStats {
+    pub count: u64,
+    pub total: Duration,
+    pub max: Duration,
+    // Events merged into an earlier pending decision
+    pub coalesced: u64,
+}
+
+impl LatencyStats {
+    pub fn record(&mut self, latency: Duration) {
+        self.count += 1;
+        self.total += latency;
+        self.max = self.max.max(latency);
+    }
+
+    pub fn mean(&self) -> Option<Duration> {
+        (self.count > 0).then(|| self.total / self.count as u32)
+    }
+}
+
+impl QuoteTrigger {
+    pub fn new(min_interval: Duration) -> QuoteTrigger {
+        QuoteTrigger {
+            min_interval,
+            pending_since: None,
+            event_received_at: None,
+            last_quote: None,
+            latency: LatencyStats::default(),
+        }
+    }
+
+    // Request a quoting decision for a market event received at `received_at`
+    pub fn notify(&mut self, received_at: Instant) {
+        match self.event_received_at {
+            Some(_) => self.latency.coalesced += 1,
+            None => self.event_received_at = Some(received_at),
+        }
+        self.follow_up(received_at);
+    }
+
+    // Request a quoting decision at `at` that no market event triggered, such as a follow-up pass
+    pub fn follow_up(&mut self, at: Instant) {
+        self.pending_since = Some(self.pending_since.map_or(at, |since| since.min(at)));
+    }
+
+    pub fn is_pending(&self) -> bool {
+        self.pending_since.is_some()
+    }
+
+    // When the pending decision may run, or `None` if nothing is pending
+    pub fn deadline(&self) -> Option<Instant> {
+        let pending_since = self.pending_since?;
+        Some(match self.last_quote {
+            Some(last_quote) => pending_since.max(last_quote + self.min_interval),
+            None => pending_since,
+        })
+    }
+
+    // Take the pending decision and return the receipt time of the market event behind it, if any
+    pub fn fire(&mut self, now: Instant) -> Option<Instant> {
+        self.pending_since = None;
+        self.last_quote = Some(now);
+        self.event_received_at.take()
+    }
+
+    // Record the first order sent for a market event received at `received_at`
+    pub fn on_sent(&mut self, received_at: Instant, sent_at: Instant) {
+        let latency = sent_at.saturating_duration_since(received_at);
+        self.latency.record(latency);
+        debug!("sent an order {:?} after the triggering event", latency);
+    }
+
+    pub fn log_latency(&self) {
+        if let Some(mean) = self.latency.mean() {
+            info!(
+                "quote latency: mean {:?}, max {:?} over {} quoting passes ({} events coalesced)",
+                mean, self.latency.max, self.latency.count, self.latency.coalesced
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn coalesces_events_and_spaces_decisions() {
+        let start = Instant::now();
+        let ms = Duration::from_millis;
+        let mut trigger = QuoteTrigger::new(ms(50));
+        assert_eq!(trigger.deadline(), None);
+
+        // A burst of deltas yields one decision, timed from the first event
+        trigger.notify(start);
+        trigger.notify(start + ms(1));
+        trigger.notify(start + ms(2));
+        assert_eq!(trigger.deadline(), Some(start));
+        assert_eq!(trigger.fire(start + ms(3)), Some(start));
+        assert_eq!(trigger.deadline(), None);
+        assert_eq!(trigger.latency.coalesced, 2);
+
+        // The next event waits for the minimum interval since the last decision
+        trigger.notify(start + ms(10));
+        assert_eq!(trigger.deadline(), Some(start + ms(53)));
+        let received_at = trigger.fire(start + ms(53)).unwrap();
+        trigger.on_sent(received_at, start + ms(60));
+        assert_eq!(trigger.latency.max, ms(50));
+        assert_eq!(trigger.latency.count, 1);
+    }
+
+    #[test]
+    fn follow_ups_are_not_timed() {
+        let start = Instant::now();
+        let ms = Duration::from_millis;
+        let mut trigger = QuoteTrigger::new(ms(50));
+
+        trigger.follow_up(start);
+        assert_eq!(trigger.deadline(), Some(start));
+        assert_eq!(trigger.fire(start + ms(1)), None);
+
+        // A later follow-up does not hold back an earlier one
+        trigger.follow_up(start + ms(60));
+        trigger.follow_up(start + ms(2));
+        // A market event arriving while a follow-up is pending is still timed
+        trigger.notify(start + ms(5));
+        assert_eq!(trigger.deadline(), Some(start + ms(51)));
+        assert_eq!(trigger.fire(start + ms(51)), Some(start + ms(5)));
+        assert_eq!(trigger.latency.coalesced, 0);
+    }
+}
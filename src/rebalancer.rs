@@ -0,0 +1,205 @@
+use crate::{
+    config::Config,
+    decimal::{Price, Size},
+    exchanges::coincheck::place_order::OrderType,
+    strategy::Side,
+};
+use log::{info, warn};
+use std::time::{Duration, Instant};
+
+// How the rebalancer takes liquidity
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RebalanceExecution {
+    // Limit order this many ticks through the opposite best price
+    AggressiveLimit { ticks: i64 },
+    Market,
+}
+
+// Hard inventory limits beyond the quoting band. Breaching one reduces the inventory in slices
+// until it is back at the edge of the band.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RebalanceRules {
+    pub hard_min: Size,
+    pub hard_max: Size,
+    pub slice_size: Size,
+    // Time between slices, for fills to come back before the next one
+    pub slice_interval: Duration,
+    pub execution: RebalanceExecution,
+}
+
+// One child order reducing the inventory
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Slice {
+    pub side: Side,
+    pub size: Size,
+    pub order_type: OrderType,
+}
+
+pub struct Rebalancer {
+    pub rules: RebalanceRules,
+    // Band to return to
+    pub min_inventory: Size,
+    pub max_inventory: Size,
+    pub price_increment: Price,
+    pub size_increment: Size,
+    pub min_order_size: Size,
+    // Side we are reducing with while a breach is being worked off
+    active: Option<Side>,
+    last_slice: Option<Instant>,
+}
+
+impl Rebalancer {
+    pub fn new(config: &Config, min_order_size: Size) -> Rebalancer {
+        Rebalancer {
+            rules: config.rebalance,
+            min_inventory: config.min_inventory,
+            max_inventory: config.max_size,
+            price_increment: config.price_increment,
+            size_increment: config.size_increment,
+            min_order_size,
+            active: None,
+            last_slice: None,
+        }
+    }
+
+    pub fn is_active(&self) -> bool {
+        self.active.is_some()
+    }
+
+    // Update the breach state for the current inventory. Returns whether rebalancing is active.
+    pub fn check(&mut self, inventory: Size) -> bool {
+        match self.active {
+            None if inventory > self.rules.hard_max => {
+                warn!(
+                    "inventory {} above the hard limit {}: selling down to {}",
+                    inventory, self.rules.hard_max, self.max_inventory
+                );
+                self.active = Some(Side::Sell);
+            }
+            None if inventory < self.rules.hard_min => {
+                warn!(
+                    "inventory {} below the hard limit {}: buying up to {}",
+                    inventory, self.rules.hard_min, self.min_inventory
+                );
+                self.active = Some(Side::Buy);
+            }
+            Some(side) if self.excess(side, inventory) < self.min_order_size => {
+                info!("inventory {} back inside the band", inventory);
+                self.active = None;
+            }
+            _ => (),
+        }
+        self.active.is_some()
+    }
+
+    // Inventory left to reduce on the given side
+    fn excess(&self, side: Side, inventory: Size) -> Size {
+        match side {
+            Side::Sell => inventory - self.max_inventory,
+            Side::Buy => self.min_inventory - inventory,
+        }
+    }
+
+    // When the next child order is due
+    pub fn next_slice_at(&self, now: Instant) -> Instant {
+        match self.last_slice {
+            Some(last_slice) => now.max(last_slice + self.rules.slice_interval),
+            None => now,
+        }
+    }
+
+    // The next child order if one is due
+    pub fn next_slice(
+        &mut self,
+        now: Instant,
+        inventory: Size,
+        best_bid: Price,
+        best_ask: Price,
+    ) -> Option<Slice> {
+        let side = self.active?;
+        if self.last_slice.is_some_and(|last_slice| {
+            now.saturating_duration_since(last_slice) < self.rules.slice_interval
+        }) {
+            return None;
+        }
+
+        let size = self
+            .rules
+            .slice_size
+            .min(self.excess(side, inventory))
+            .floor_to(self.size_increment)
+            .max(self.min_order_size);
+        let ticks = match self.rules.execution {
+            RebalanceExecution::AggressiveLimit { ticks } => ticks,
+            RebalanceExecution::Market => 0,
+        };
+        // Cross the spread: sell into the bid, buy from the ask
+        let price = match side {
+            Side::Sell => best_bid - self.price_increment.times(ticks),
+            Side::Buy => best_ask + self.price_increment.times(ticks),
+        };
+        let order_type = match self.rules.execution {
+            RebalanceExecution::AggressiveLimit { .. } => OrderType::Limit { price },
+            RebalanceExecution::Market => OrderType::Market {
+                expected_price: price,
+            },
+        };
+
+        self.last_slice = Some(now);
+        let slice = Slice {
+            side,
+            size,
+            order_type,
+        };
+        info!("rebalancing inventory {}: {:?}", inventory, slice);
+        Some(slice)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn size(value: &str) -> Size {
+        value.parse().unwrap()
+    }
+
+    #[test]
+    fn sells_down_in_slices_until_inside_the_band() -> anyhow::Result<()> {
+        let mut config = Config::new("btc_jpy", 0.02, 1.0, 0.00000001)?;
+        config.max_size = size("0.1");
+        config.rebalance.hard_max = size("0.15");
+        config.rebalance.slice_size = size("0.03");
+        config.rebalance.execution = RebalanceExecution::AggressiveLimit { ticks: 2 };
+        let mut rebalancer = Rebalancer::new(&config, size("0.005"));
+        let (bid, ask) = ("100".parse()?, "101".parse()?);
+        let now = Instant::now();
+
+        assert!(!rebalancer.check(size("0.12")));
+        assert!(rebalancer.check(size("0.16")));
+        assert_eq!(
+            rebalancer.next_slice(now, size("0.16"), bid, ask),
+            Some(Slice {
+                side: Side::Sell,
+                size: size("0.03"),
+                order_type: OrderType::Limit {
+                    price: "98".parse()?
+                },
+            })
+        );
+        // Wait for the slice interval
+        assert_eq!(rebalancer.next_slice(now, size("0.13"), bid, ask), None);
+        let later = now + config.rebalance.slice_interval;
+        assert_eq!(rebalancer.next_slice_at(now), later);
+        assert_eq!(
+            rebalancer
+                .next_slice(later, size("0.11"), bid, ask)
+                .map(|slice| slice.size),
+            Some(size("0.01"))
+        );
+        // Stays active inside the hard limit until the band is reached
+        assert!(rebalancer.check(size("0.11")));
+        assert!(!rebalancer.check(size("0.1")));
+        Ok(())
+    }
+}
@@ -0,0 +1,202 @@
+use crate::{
+    config::Config,
+    decimal::{Price, Size},
+    exchanges::coincheck::Order,
+    strategy::{Quote, Side},
+};
+use log::warn;
+use std::time::{Duration, Instant};
+
+// A change to our live orders needed to reach the desired quotes
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Action {
+    Cancel { order_id: usize },
+    Place(Quote),
+    // Coincheck cannot modify an order, so an amend is a cancel followed by a place
+    Amend { order_id: usize, quote: Quote },
+}
+
+// Diffs the desired quotes against the live orders. An order within the tolerances of a desired
+// quote is left alone so it keeps its place in the queue.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Reconciler {
+    // How far an order may sit behind its desired price, i.e. no longer at the top
+    pub passive_tolerance: Price,
+    // How far an order may sit ahead of its desired price, e.g. after the price moved in our favor
+    pub aggressive_tolerance: Price,
+    pub size_tolerance: Size,
+    // Orders younger than this are not moved, only cancelled when their side is no longer quoted
+    pub min_order_age: Duration,
+}
+
+impl Reconciler {
+    pub fn new(config: &Config) -> Reconciler {
+        let requote = config.requote;
+        Reconciler {
+            passive_tolerance: config.price_increment.times(requote.passive_ticks),
+            aggressive_tolerance: config.price_increment.times(requote.aggressive_ticks),
+            size_tolerance: requote.size_tolerance,
+            min_order_age: requote.min_order_age,
+        }
+    }
+
+    fn matches(&self, order: &Order, quote: &Quote) -> bool {
+        // Positive when the order is more aggressive than the quote
+        let ahead = match quote.side {
+            Side::Buy => order.price - quote.price,
+            Side::Sell => quote.price - order.price,
+        };
+        ahead <= self.aggressive_tolerance
+            && -ahead <= self.passive_tolerance
+            && (order.size - quote.size).abs() <= self.size_tolerance
+    }
+
+    fn is_young(&self, order: &Order, now: Instant) -> bool {
+        order
+            .placed_at
+            .is_some_and(|placed_at| now.saturating_duration_since(placed_at) < self.min_order_age)
+    }
+
+    // The minimal set of actions turning `live` into `desired`, cancels first
+    pub fn reconcile(&self, desired: &[Quote], live: &[Order], now: Instant) -> Vec<Action> {
+        let mut cancels = Vec::new();
+        let mut others = Vec::new();
+        for side in [Side::Buy, Side::Sell] {
+            let mut quotes: Vec<&Quote> = desired.iter().filter(|q| q.side == side).collect();
+            let mut orders: Vec<&Order> = Vec::new();
+            for order in live {
+                match order.side.parse::<Side>() {
+                    Ok(order_side) if order_side == side => orders.push(order),
+                    Ok(_) => (),
+                    Err(e) => warn!("ignoring order {}: {:?}", order.id, e),
+                }
+            }
+
+            // Keep orders that already satisfy a quote
+            orders.retain(|order| {
+                match quotes.iter().position(|quote| self.matches(order, quote)) {
+                    Some(index) => {
+                        quotes.remove(index);
+                        false
+                    }
+                    None => true,
+                }
+            });
+
+            // Young orders stand in for a remaining quote until they are old enough to move
+            orders.retain(|order| {
+                if self.is_young(order, now) && !quotes.is_empty() {
+                    quotes.remove(0);
+                    false
+                } else {
+                    true
+                }
+            });
+
+            // Move the remaining orders onto the remaining quotes, cancel or place the rest
+            let mut orders = orders.into_iter();
+            for quote in quotes {
+                match orders.next() {
+                    Some(order) => others.push(Action::Amend {
+                        order_id: order.id,
+                        quote: *quote,
+                    }),
+                    None => others.push(Action::Place(*quote)),
+                }
+            }
+            cancels.extend(orders.map(|order| Action::Cancel { order_id: order.id }));
+        }
+        cancels.extend(others);
+        cancels
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn order(id: usize, side: &str, price: &str, size: &str) -> Order {
+        Order {
+            id,
+            side: side.to_string(),
+            price: price.parse().unwrap(),
+            size: size.parse().unwrap(),
+            placed_at: None,
+        }
+    }
+
+    fn quote(side: Side, price: &str, size: &str) -> Quote {
+        Quote {
+            side,
+            price: price.parse().unwrap(),
+            size: size.parse().unwrap(),
+        }
+    }
+
+    fn reconciler() -> Reconciler {
+        Reconciler {
+            passive_tolerance: Price::ZERO,
+            aggressive_tolerance: "1".parse().unwrap(),
+            size_tolerance: "0.01".parse().unwrap(),
+            min_order_age: Duration::ZERO,
+        }
+    }
+
+    #[test]
+    fn issues_minimal_actions() {
+        let reconciler = reconciler();
+        let now = Instant::now();
+        let live = vec![
+            order(1, "buy", "101", "0.02"),
+            order(2, "sell", "105", "0.02"),
+            order(3, "sell", "106", "0.02"),
+        ];
+        let desired = vec![
+            // Order 1 is one tick more aggressive, within tolerance
+            quote(Side::Buy, "100", "0.015"),
+            quote(Side::Sell, "103", "0.02"),
+        ];
+
+        assert_eq!(
+            reconciler.reconcile(&desired, &live, now),
+            vec![
+                Action::Cancel { order_id: 3 },
+                Action::Amend {
+                    order_id: 2,
+                    quote: desired[1],
+                },
+            ]
+        );
+        assert_eq!(
+            reconciler.reconcile(&desired[..1], &[], now),
+            vec![Action::Place(desired[0])]
+        );
+    }
+
+    #[test]
+    fn requotes_only_when_behind_or_too_aggressive() {
+        let mut reconciler = reconciler();
+        let now = Instant::now();
+        let live = vec![order(1, "buy", "100", "0.02")];
+
+        // Someone bid higher: we are no longer at the top
+        let behind = [quote(Side::Buy, "101", "0.02")];
+        assert_eq!(reconciler.reconcile(&behind, &live, now).len(), 1);
+        // The best bid dropped by one tick: stay, by two ticks: pull back
+        assert!(reconciler
+            .reconcile(&[quote(Side::Buy, "99", "0.02")], &live, now)
+            .is_empty());
+        assert_eq!(
+            reconciler
+                .reconcile(&[quote(Side::Buy, "98", "0.02")], &live, now)
+                .len(),
+            1
+        );
+
+        // A fresh order is not moved
+        reconciler.min_order_age = Duration::from_secs(1);
+        let mut young = live[0].clone();
+        young.placed_at = Some(now);
+        assert!(reconciler.reconcile(&behind, &[young], now).is_empty());
+    }
+}
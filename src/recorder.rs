@@ -0,0 +1,228 @@
+use crate::{
+    bus::{MarketEvent, Subscriber},
+    exchanges::coincheck::{get_order_books, OrderBookData},
+    util::{date_from_timestamp, get_timestamp},
+};
+use anyhow::Result;
+use flate2::{write::GzEncoder, Compression};
+use log::{debug, info, warn};
+use reqwest::Client;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::{
+    collections::HashMap,
+    fs::{self, File, OpenOptions},
+    io::Write,
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+// A single line of a recording, stamped with the local receive time in milliseconds
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum Record {
+    // Raw WebSocket message as received from Coincheck
+    Message {
+        received_at: u64,
+        data: Value,
+    },
+    // Order book snapshot fetched from the REST API
+    Snapshot {
+        received_at: u64,
+        pair: String,
+        data: OrderBookData,
+    },
+}
+
+impl Record {
+    pub fn received_at(&self) -> u64 {
+        match self {
+            Record::Message { received_at, .. } | Record::Snapshot { received_at, .. } => {
+                *received_at
+            }
+        }
+    }
+}
+
+// Extract the pair a raw WebSocket message belongs to
+pub fn message_pair(data: &Value) -> Option<&str> {
+    match data.get(0)? {
+        // Order book updates look like ["btc_jpy", {...}]
+        Value::String(pair) => Some(pair),
+        // Trades look like [["timestamp", "id", "btc_jpy", ...], ...]
+        Value::Array(trade) => trade.get(2)?.as_str(),
+        _ => None,
+    }
+}
+
+// Path of the recording for a pair on a given date, made by the session started at `session`
+pub fn file_path(dir: &Path, pair: &str, date: &str, session: u64) -> PathBuf {
+    dir.join(pair)
+        .join(format!("{}-{}-{}.jsonl.gz", pair, date, session))
+}
+
+struct DailyFile {
+    date: String,
+    encoder: GzEncoder<File>,
+}
+
+// Writes records to compressed JSONL files, one per pair per UTC day and per session
+pub struct Recorder {
+    dir: PathBuf,
+    // Start time of this session in milliseconds
+    session: u64,
+    files: HashMap<String, DailyFile>,
+}
+
+impl Recorder {
+    pub fn new(dir: &str) -> Result<Recorder> {
+        fs::create_dir_all(dir)?;
+        Ok(Recorder {
+            dir: PathBuf::from(dir),
+            session: get_timestamp()?,
+            files: HashMap::new(),
+        })
+    }
+
+    pub fn record(&mut self, pair: &str, record: &Record) -> Result<()> {
+        let date = date_from_timestamp(record.received_at());
+
+        // Rotate the file when the day changes
+        if let Some(file) = self.files.get(pair) {
+            if file.date != date {
+                if let Some(file) = self.files.remove(pair) {
+                    file.encoder.finish()?;
+                }
+            }
+        }
+
+        if !self.files.contains_key(pair) {
+            let path = file_path(&self.dir, pair, &date, self.session);
+            if let Some(parent) = path.parent() {
+                fs::create_dir_all(parent)?;
+            }
+            // Each session writes its own files, so a gzip stream left unfinished by a crash is
+            // never continued
+            let file = OpenOptions::new()
+                .write(true)
+                .create_new(true)
+                .open(&path)?;
+            info!("recording {} to {}", pair, path.display());
+            self.files.insert(
+                pair.to_string(),
+                DailyFile {
+                    date: date.clone(),
+                    encoder: GzEncoder::new(file, Compression::default()),
+                },
+            );
+        }
+
+        if let Some(file) = self.files.get_mut(pair) {
+            serde_json::to_writer(&mut file.encoder, record)?;
+            file.encoder.write_all(b"\n")?;
+        }
+        Ok(())
+    }
+
+    // Flush buffered records so the files are readable up to this point
+    pub fn flush(&mut self) -> Result<()> {
+        for file in self.files.values_mut() {
+            file.encoder.flush()?;
+        }
+        Ok(())
+    }
+
+    pub fn finish(mut self) -> Result<()> {
+        for (_, file) in self.files.drain() {
+            file.encoder.finish()?;
+        }
+        Ok(())
+    }
+}
+
+// Fetch and record an order book snapshot for every pair
+async fn record_snapshots(
+    client: &Client,
+    pairs: &[String],
+    recorder: &mut Recorder,
+) -> Result<()> {
+    for pair in pairs {
+        match get_order_books::get_order_books(client, pair).await {
+            Ok(order_book) => {
+                let record = Record::Snapshot {
+                    received_at: get_timestamp()?,
+                    pair: pair.clone(),
+                    data: order_book,
+                };
+                recorder.record(pair, &record)?;
+            }
+            Err(e) => warn!("failed to fetch order book snapshot for {}: {:?}", pair, e),
+        }
+    }
+    Ok(())
+}
+
+// Record raw messages from the bus and periodic REST snapshots until shutdown or the bus is closed
+pub async fn run(
+    mut subscriber: Subscriber,
+    client: Client,
+    dir: &str,
+    pairs: Vec<String>,
+    snapshot_interval: Duration,
+) -> Result<()> {
+    let mut recorder = Recorder::new(dir)?;
+    let mut snapshot_interval = tokio::time::interval(snapshot_interval);
+
+    loop {
+        tokio::select! {
+            event = subscriber.recv() => {
+                match event {
+                    Some(MarketEvent::Message { received_at, text }) => {
+                        let data: Value = serde_json::from_str(&text)?;
+                        match message_pair(&data).map(str::to_string) {
+                            Some(pair) => recorder.record(&pair, &Record::Message { received_at, data })?,
+                            None => debug!("not recording message without a pair: {}", text),
+                        }
+                    }
+                    // Replay resyncs the book from the snapshot taken right after the gap
+                    Some(MarketEvent::Lagged(skipped)) => {
+                        warn!("recorder dropped {} messages, recording fresh snapshots", skipped);
+                        record_snapshots(&client, &pairs, &mut recorder).await?;
+                    }
+                    Some(MarketEvent::Shutdown) | None => break,
+                    Some(_) => (),
+                }
+            }
+            _ = snapshot_interval.tick() => {
+                record_snapshots(&client, &pairs, &mut recorder).await?;
+                recorder.flush()?;
+            }
+        }
+    }
+
+    recorder.finish()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn finds_the_pair_of_a_message() {
+        let order_book = json!(["btc_jpy", { "bids": [], "asks": [] }]);
+        let trades = json!([[
+            "1663318663",
+            "2357062",
+            "eth_jpy",
+            "1.0",
+            "2.0",
+            "sell",
+            "1",
+            "2"
+        ]]);
+        assert_eq!(message_pair(&order_book), Some("btc_jpy"));
+        assert_eq!(message_pair(&trades), Some("eth_jpy"));
+        assert_eq!(message_pair(&json!({})), None);
+    }
+}
@@ -0,0 +1,208 @@
+use crate::{
+    exchanges::coincheck::{CoincheckWebsocketEvent, OrderBookData, State},
+    recorder::Record,
+};
+use anyhow::Result;
+use flate2::read::MultiGzDecoder;
+use log::warn;
+use std::{
+    fs::File,
+    io::{BufRead, BufReader, Lines},
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+// Records of a recording, read one line at a time so a full day never has to fit in memory.
+// A truncated tail, e.g. from a crash, ends the recording early.
+pub struct Records {
+    path: PathBuf,
+    lines: Option<Lines<BufReader<MultiGzDecoder<File>>>>,
+}
+
+impl Iterator for Records {
+    type Item = Result<Record>;
+
+    fn next(&mut self) -> Option<Result<Record>> {
+        match self.lines.as_mut()?.next()? {
+            Ok(line) => Some(serde_json::from_str(&line).map_err(Into::into)),
+            Err(e) => {
+                warn!(
+                    "stopping at unreadable tail of {}: {}",
+                    self.path.display(),
+                    e
+                );
+                self.lines = None;
+                None
+            }
+        }
+    }
+}
+
+pub fn read_records(path: &Path) -> Result<Records> {
+    let reader = BufReader::new(MultiGzDecoder::new(File::open(path)?));
+    Ok(Records {
+        path: path.to_path_buf(),
+        lines: Some(reader.lines()),
+    })
+}
+
+// How fast recorded time passes during a replay
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Speed {
+    // Wait between events exactly as long as they were apart when recorded
+    RealTime,
+    // Wait between events for the recorded gap divided by the factor
+    Accelerated(f64),
+    // Do not wait at all
+    Max,
+}
+
+// An event emitted by the replay, in the same shape the live pipeline receives it
+#[derive(Debug, Clone)]
+pub enum ReplayEvent {
+    Snapshot {
+        pair: String,
+        order_book: OrderBookData,
+    },
+    Event(CoincheckWebsocketEvent),
+}
+
+pub struct Replay {
+    records: Box<dyn Iterator<Item = Result<Record>> + Send>,
+    speed: Speed,
+    // Receive time of the last replayed event in milliseconds since the Unix epoch. It only paces
+    // the replay; `State` still reads the wall clock, e.g. for snapshot timestamps.
+    pub clock: Option<u64>,
+}
+
+impl Replay {
+    pub fn new(records: Vec<Record>, speed: Speed) -> Replay {
+        Replay::from_records(records.into_iter().map(Ok), speed)
+    }
+
+    pub fn from_file(path: &Path, speed: Speed) -> Result<Replay> {
+        Ok(Replay::from_records(read_records(path)?, speed))
+    }
+
+    fn from_records(
+        records: impl Iterator<Item = Result<Record>> + Send + 'static,
+        speed: Speed,
+    ) -> Replay {
+        Replay {
+            records: Box::new(records),
+            speed,
+            clock: None,
+        }
+    }
+
+    // Wait until the next event is due and return it, advancing the clock
+    pub async fn next(&mut self) -> Option<Result<ReplayEvent>> {
+        let record = match self.records.next()? {
+            Ok(record) => record,
+            Err(e) => return Some(Err(e)),
+        };
+        let received_at = record.received_at();
+
+        if let Some(clock) = self.clock {
+            let gap = Duration::from_millis(received_at.saturating_sub(clock));
+            match self.speed {
+                Speed::RealTime => tokio::time::sleep(gap).await,
+                Speed::Accelerated(factor) if factor > 0.0 => {
+                    tokio::time::sleep(gap.div_f64(factor)).await
+                }
+                Speed::Accelerated(_) | Speed::Max => (),
+            }
+        }
+        self.clock = Some(received_at);
+
+        Some(match record {
+            Record::Snapshot { pair, data, .. } => Ok(ReplayEvent::Snapshot {
+                pair,
+                order_book: data,
+            }),
+            Record::Message { data, .. } => serde_json::from_value(data)
+                .map(ReplayEvent::Event)
+                .map_err(Into::into),
+        })
+    }
+
+    // Feed every remaining event into the state as the live loop in main would
+    pub async fn run(&mut self, state: &mut State) -> Result<()> {
+        while let Some(event) = self.next().await {
+            match event? {
+                ReplayEvent::Snapshot { order_book, .. } => state.set_order_book(&order_book),
+                ReplayEvent::Event(CoincheckWebsocketEvent::OrderBookUpdateEvent(response)) => {
+                    state.update_order_book(&response.1)
+                }
+                ReplayEvent::Event(CoincheckWebsocketEvent::ExecutionEvent(executions)) => {
+                    state.handle_execution_events(executions);
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[tokio::test]
+    async fn replays_snapshot_then_deltas() -> Result<()> {
+        let records = vec![
+            Record::Snapshot {
+                received_at: 1_000,
+                pair: "btc_jpy".to_string(),
+                data: serde_json::from_value(json!({
+                    "bids": [["100.0", "1.0"]],
+                    "asks": [["101.0", "1.0"]],
+                }))?,
+            },
+            Record::Message {
+                received_at: 1_500,
+                data: json!(["btc_jpy", { "bids": [["100.5", "2.0"]], "asks": [] }]),
+            },
+        ];
+        let mut state = State::new("btc_jpy")?;
+        let mut replay = Replay::new(records, Speed::Max);
+
+        replay.run(&mut state).await?;
+
+        assert_eq!(replay.clock, Some(1_500));
+        assert_eq!(state.best_bid_price, Some("100.5".parse()?));
+        assert_eq!(state.best_ask_price, Some("101".parse()?));
+        Ok(())
+    }
+
+    #[test]
+    fn reads_records_from_a_file_one_at_a_time() -> Result<()> {
+        use flate2::{write::GzEncoder, Compression};
+        use std::io::Write;
+
+        let path = std::env::temp_dir().join(format!("replay-{}.jsonl.gz", std::process::id()));
+        let mut encoder = GzEncoder::new(File::create(&path)?, Compression::default());
+        for received_at in [1_000, 2_000] {
+            let record = Record::Message {
+                received_at,
+                data: json!(["btc_jpy", { "bids": [], "asks": [] }]),
+            };
+            serde_json::to_writer(&mut encoder, &record)?;
+            encoder.write_all(b"\n")?;
+        }
+        encoder.finish()?;
+
+        let mut records = read_records(&path)?;
+        assert_eq!(
+            records.next().transpose()?.map(|r| r.received_at()),
+            Some(1_000)
+        );
+        assert_eq!(
+            records.next().transpose()?.map(|r| r.received_at()),
+            Some(2_000)
+        );
+        assert!(records.next().is_none());
+        std::fs::remove_file(&path)?;
+        Ok(())
+    }
+}
@@ -0,0 +1,119 @@
+pub mod avellaneda_stoikov;
+pub mod inventory_bands;
+pub mod join_best;
+pub mod volatility_adaptive;
+
+use crate::{
+    analytics::BookStats,
+    bus::MarketEvent,
+    config::{Config, StrategyKind},
+    decimal::{Price, Size},
+    exchanges::coincheck::{subscriptions::SubscriptionHandle, Fill, Order, MIN_ORDER_SIZE},
+    fees::Fees,
+    order_book::OrderBook,
+    trade_tape::TradeTape,
+};
+use anyhow::{anyhow, Error, Result};
+use avellaneda_stoikov::AvellanedaStoikov;
+use inventory_bands::InventoryBands;
+use join_best::JoinBest;
+use log::warn;
+use std::str::FromStr;
+use volatility_adaptive::VolatilityAdaptive;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Side {
+    Buy,
+    Sell,
+}
+
+impl Side {
+    // Order type as used by the Coincheck API
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Side::Buy => "buy",
+            Side::Sell => "sell",
+        }
+    }
+}
+
+impl FromStr for Side {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Side> {
+        match s {
+            "buy" => Ok(Side::Buy),
+            "sell" => Ok(Side::Sell),
+            _ => Err(anyhow!("unknown side: {:?}", s)),
+        }
+    }
+}
+
+// An order the strategy wants resting on the book
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Quote {
+    pub side: Side,
+    pub price: Price,
+    pub size: Size,
+}
+
+// Market and account state handed to a strategy for one quoting decision
+pub struct Snapshot<'a> {
+    pub symbol: &'a str,
+    // Wall clock time of the decision in milliseconds since the Unix epoch
+    pub timestamp: u64,
+    pub order_book: &'a OrderBook,
+    pub book_stats: &'a BookStats,
+    pub trade_tape: &'a TradeTape,
+    // Best prices of other participants, ignoring our own orders
+    pub best_bid: Price,
+    pub best_ask: Price,
+    pub fees: Fees,
+    pub btc_balance: Size,
+    pub buy_orders: &'a [Order],
+    pub sell_orders: &'a [Order],
+}
+
+// Decides which quotes we want on the book. Strategies only make decisions; sending and
+// cancelling orders is left to `State`.
+pub trait Strategy {
+    fn name(&self) -> &str;
+
+    // Desired quotes. Live orders not matching any quote are cancelled.
+    fn quotes(&mut self, snapshot: &Snapshot) -> Vec<Quote>;
+
+    // Called for every fill of one of our orders
+    fn on_fill(&mut self, _fill: &Fill) {}
+
+    // Called once before quoting starts with a handle to subscribe to other channels, such as
+    // the order book of a related pair
+    fn on_start(&mut self, _subscriptions: SubscriptionHandle) {}
+
+    // Called for order book updates and trades of pairs other than the one being quoted
+    fn on_market_event(&mut self, _event: &MarketEvent) {}
+}
+
+// Build the strategy selected in the config, adapted to volatility when that is enabled
+pub fn from_config(config: &Config) -> Result<Box<dyn Strategy>> {
+    let strategy: Box<dyn Strategy> = match config.strategy {
+        StrategyKind::JoinBest => Box::new(JoinBest::new(config, MIN_ORDER_SIZE)),
+        StrategyKind::InventoryBands => Box::new(InventoryBands::new(config, MIN_ORDER_SIZE)?),
+        StrategyKind::AvellanedaStoikov => {
+            Box::new(AvellanedaStoikov::new(config, MIN_ORDER_SIZE)?)
+        }
+    };
+    Ok(match config.volatility {
+        // Avellaneda-Stoikov already prices in volatility
+        Some(_) if config.strategy == StrategyKind::AvellanedaStoikov => {
+            warn!("ignoring the volatility rules: the strategy already models volatility");
+            strategy
+        }
+        Some(rules) => Box::new(VolatilityAdaptive::new(
+            strategy,
+            config,
+            rules,
+            MIN_ORDER_SIZE,
+        )),
+        None => strategy,
+    })
+}
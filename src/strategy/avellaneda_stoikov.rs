@@ -0,0 +1,129 @@
+use super::{inventory_bands::InventoryBands, Quote, Side, Snapshot, Strategy};
+use crate::{
+    config::{AvellanedaStoikovParams, Config},
+    decimal::{Price, Size},
+    placement::QuoteRules,
+};
+use anyhow::{ensure, Result};
+use log::info;
+
+// Market making around a reservation price (Avellaneda & Stoikov, 2008). Inventory above the
+// target lowers the reservation price below the mid so the ask fills more easily, and higher
+// volatility widens the spread. Sizes and levels follow the inventory bands and the ladder.
+pub struct AvellanedaStoikov {
+    pub params: AvellanedaStoikovParams,
+    pub bands: InventoryBands,
+    pub quote_rules: QuoteRules,
+    pub price_increment: Price,
+}
+
+// Result of one quoting decision
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct FairValue {
+    pub mid: f64,
+    // Variance of the price over the horizon
+    pub variance: f64,
+    pub reservation_price: f64,
+    // Optimal distance between bid and ask
+    pub spread: f64,
+}
+
+impl AvellanedaStoikov {
+    pub fn new(config: &Config, min_order_size: Size) -> Result<AvellanedaStoikov> {
+        let params = config.avellaneda_stoikov;
+        ensure!(
+            params.risk_aversion > 0.0 && params.liquidity > 0.0,
+            "risk aversion and liquidity must be positive"
+        );
+        Ok(AvellanedaStoikov {
+            params,
+            bands: InventoryBands::new(config, min_order_size)?,
+            quote_rules: config.quote_rules,
+            price_increment: config.price_increment,
+        })
+    }
+
+    // Reservation price and spread for a mid price, an inventory in multiples of the order size
+    // and the volatility of log returns over the volatility window
+    pub fn fair_value(&self, mid: f64, inventory: f64, volatility: f64) -> FairValue {
+        let gamma = self.params.risk_aversion;
+        let window = self.params.volatility_window.as_secs_f64().max(1.0);
+        let variance = (mid * volatility).powi(2) / window * self.params.horizon.as_secs_f64();
+        FairValue {
+            mid,
+            variance,
+            reservation_price: mid - inventory * gamma * variance,
+            spread: gamma * variance + 2.0 / gamma * (1.0 + gamma / self.params.liquidity).ln(),
+        }
+    }
+}
+
+impl Strategy for AvellanedaStoikov {
+    fn name(&self) -> &str {
+        "avellaneda_stoikov"
+    }
+
+    fn quotes(&mut self, snapshot: &Snapshot) -> Vec<Quote> {
+        let tick = self.price_increment;
+        let mid = (snapshot.best_bid.to_f64() + snapshot.best_ask.to_f64()) / 2.0;
+        let stats = snapshot.trade_tape.stats(
+            snapshot.symbol,
+            self.params.volatility_window,
+            snapshot.timestamp,
+        );
+        let inventory = (snapshot.btc_balance - self.bands.target_inventory).to_f64()
+            / self.bands.size.to_f64();
+        let fair_value = self.fair_value(mid, inventory, stats.volatility.unwrap_or_default());
+
+        // Never tighter than the minimum spread, and never crossing the other side of the book
+        let spread = fair_value
+            .spread
+            .max((tick.times(self.quote_rules.min_spread(mid, tick, &snapshot.fees))).to_f64());
+        let bid = Price::from_f64(fair_value.reservation_price - spread / 2.0)
+            .floor_to(tick)
+            .min(snapshot.best_ask - tick);
+        let ask = Price::from_f64(fair_value.reservation_price + spread / 2.0)
+            .ceil_to(tick)
+            .max(snapshot.best_bid + tick);
+        info!(
+            "fair value {:.0} (mid {:.0}, inventory {:.2}, volatility {:.6}), spread {:.0}: bid {} ask {}",
+            fair_value.reservation_price,
+            mid,
+            inventory,
+            stats.volatility.unwrap_or_default(),
+            spread,
+            bid,
+            ask
+        );
+
+        let mut quotes = self.bands.side_quotes(Side::Buy, bid, snapshot.btc_balance);
+        quotes.extend(
+            self.bands
+                .side_quotes(Side::Sell, ask, snapshot.btc_balance),
+        );
+        quotes
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn skews_reservation_price_with_inventory() -> Result<()> {
+        let mut config = Config::new("btc_jpy", 0.02, 1.0, 0.00000001)?;
+        config.max_size = "0.1".parse()?;
+        config.target_inventory = "0.05".parse()?;
+        let strategy = AvellanedaStoikov::new(&config, "0.005".parse()?)?;
+
+        let flat = strategy.fair_value(10_000_000.0, 0.0, 0.001);
+        let long = strategy.fair_value(10_000_000.0, 1.0, 0.001);
+        let volatile = strategy.fair_value(10_000_000.0, 0.0, 0.002);
+
+        assert_eq!(flat.reservation_price, 10_000_000.0);
+        assert!(long.reservation_price < flat.reservation_price);
+        assert_eq!(long.spread, flat.spread);
+        assert!(volatile.spread > flat.spread);
+        Ok(())
+    }
+}
@@ -0,0 +1,161 @@
+use super::{Quote, Side, Snapshot, Strategy};
+use crate::{
+    config::Config,
+    decimal::{Price, Size},
+    placement::{Ladder, QuoteRules},
+};
+use anyhow::{ensure, Result};
+
+// Quote both sides at the prices given by the quote rules, laddered per the config. The side that
+// would move the inventory further from the target is scaled down linearly, reaching zero at the
+// band edge, while the side that moves it back keeps the full size.
+pub struct InventoryBands {
+    pub size: Size,
+    pub min_inventory: Size,
+    pub target_inventory: Size,
+    pub max_inventory: Size,
+    pub size_increment: Size,
+    pub min_order_size: Size,
+    pub quote_rules: QuoteRules,
+    pub price_increment: Price,
+    pub ladder: Ladder,
+}
+
+impl InventoryBands {
+    pub fn new(config: &Config, min_order_size: Size) -> Result<InventoryBands> {
+        ensure!(
+            config.min_inventory <= config.target_inventory
+                && config.target_inventory <= config.max_size,
+            "inventory bands must satisfy min {} <= target {} <= max {}",
+            config.min_inventory,
+            config.target_inventory,
+            config.max_size
+        );
+        Ok(InventoryBands {
+            size: config.size,
+            min_inventory: config.min_inventory,
+            target_inventory: config.target_inventory,
+            max_inventory: config.max_size,
+            size_increment: config.size_increment,
+            min_order_size,
+            quote_rules: config.quote_rules,
+            price_increment: config.price_increment,
+            ladder: config.ladder.clone(),
+        })
+    }
+
+    // Size left before the band edge on one side
+    pub fn room(&self, side: Side, inventory: Size) -> Size {
+        match side {
+            Side::Buy => self.max_inventory - inventory,
+            Side::Sell => inventory - self.min_inventory,
+        }
+    }
+
+    // Size to quote at the best level of one side, or `None` if it is below the minimum
+    pub fn quote_size(&self, side: Side, inventory: Size) -> Option<Size> {
+        // Room left before the band edge, and the distance from the target to that edge
+        let (room, band) = match side {
+            Side::Buy => (
+                self.max_inventory - inventory,
+                self.max_inventory - self.target_inventory,
+            ),
+            Side::Sell => (
+                inventory - self.min_inventory,
+                self.target_inventory - self.min_inventory,
+            ),
+        };
+        let moves_away = match side {
+            Side::Buy => inventory >= self.target_inventory,
+            Side::Sell => inventory <= self.target_inventory,
+        };
+
+        let mut size = self.size;
+        if moves_away && band > Size::ZERO {
+            let scale = (room.to_f64() / band.to_f64()).clamp(0.0, 1.0);
+            size = Size::from_f64(size.to_f64() * scale);
+        }
+        let size = size.min(room).floor_to(self.size_increment);
+        (size >= self.min_order_size).then_some(size)
+    }
+
+    // Ladder of quotes for one side starting at `price`, within the room left in the band
+    pub fn side_quotes(&self, side: Side, price: Price, inventory: Size) -> Vec<Quote> {
+        let Some(size) = self.quote_size(side, inventory) else {
+            return Vec::new();
+        };
+        let room = self.room(side, inventory);
+        self.ladder
+            .quotes(side, price, size, room, self.price_increment)
+            .into_iter()
+            .map(|quote| Quote {
+                size: quote.size.floor_to(self.size_increment),
+                ..quote
+            })
+            .filter(|quote| quote.size >= self.min_order_size)
+            .collect()
+    }
+}
+
+impl Strategy for InventoryBands {
+    fn name(&self) -> &str {
+        "inventory_bands"
+    }
+
+    fn quotes(&mut self, snapshot: &Snapshot) -> Vec<Quote> {
+        let inventory = snapshot.btc_balance;
+        let (bid_price, ask_price) = self.quote_rules.prices(
+            snapshot.best_bid,
+            snapshot.best_ask,
+            self.price_increment,
+            &snapshot.fees,
+        );
+
+        let mut quotes = self.side_quotes(Side::Buy, bid_price, inventory);
+        quotes.extend(self.side_quotes(Side::Sell, ask_price, inventory));
+        quotes
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn size(value: &str) -> Size {
+        value.parse().unwrap()
+    }
+
+    #[test]
+    fn scales_down_the_side_moving_away_from_target() -> Result<()> {
+        let mut config = Config::new("btc_jpy", 0.02, 1.0, 0.00000001)?;
+        config.max_size = size("0.1");
+        config.target_inventory = size("0.05");
+        let strategy = InventoryBands::new(&config, size("0.005"))?;
+
+        // At the target both sides quote the full size
+        assert_eq!(
+            strategy.quote_size(Side::Buy, size("0.05")),
+            Some(size("0.02"))
+        );
+        assert_eq!(
+            strategy.quote_size(Side::Sell, size("0.05")),
+            Some(size("0.02"))
+        );
+        // Long: buying is scaled down, selling is not
+        assert_eq!(
+            strategy.quote_size(Side::Buy, size("0.075")),
+            Some(size("0.01"))
+        );
+        assert_eq!(
+            strategy.quote_size(Side::Sell, size("0.075")),
+            Some(size("0.02"))
+        );
+        // At the band edge only the reducing side quotes
+        assert_eq!(strategy.quote_size(Side::Buy, size("0.1")), None);
+        assert_eq!(strategy.quote_size(Side::Sell, size("0.003")), None);
+
+        config.target_inventory = size("0.2");
+        assert!(InventoryBands::new(&config, size("0.005")).is_err());
+        Ok(())
+    }
+}
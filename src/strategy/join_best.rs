@@ -0,0 +1,110 @@
+use super::{Quote, Side, Snapshot, Strategy};
+use crate::{
+    config::Config,
+    decimal::{Price, Size},
+    placement::QuoteRules,
+};
+
+// Quote the bid while holding less than `flip_size` BTC, otherwise quote the ask with the whole
+// balance. With the default quote rules this joins the best bid or ask.
+pub struct JoinBest {
+    pub size: Size,
+    pub flip_size: Size,
+    pub quote_rules: QuoteRules,
+    pub price_increment: Price,
+}
+
+impl JoinBest {
+    pub fn new(config: &Config, flip_size: Size) -> JoinBest {
+        JoinBest {
+            size: config.size,
+            flip_size,
+            quote_rules: config.quote_rules,
+            price_increment: config.price_increment,
+        }
+    }
+}
+
+impl Strategy for JoinBest {
+    fn name(&self) -> &str {
+        "join_best"
+    }
+
+    fn quotes(&mut self, snapshot: &Snapshot) -> Vec<Quote> {
+        let (bid_price, ask_price) = self.quote_rules.prices(
+            snapshot.best_bid,
+            snapshot.best_ask,
+            self.price_increment,
+            &snapshot.fees,
+        );
+        if snapshot.btc_balance < self.flip_size {
+            vec![Quote {
+                side: Side::Buy,
+                price: bid_price,
+                size: self.size,
+            }]
+        } else {
+            vec![Quote {
+                side: Side::Sell,
+                price: ask_price,
+                size: snapshot.btc_balance,
+            }]
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        analytics::{BookStats, DEPTH_LEVELS},
+        exchanges::coincheck::OrderBookData,
+        order_book::OrderBook,
+        trade_tape::TradeTape,
+    };
+    use serde_json::json;
+    use std::time::Duration;
+
+    #[test]
+    fn flips_between_bid_and_ask() {
+        let data: OrderBookData = serde_json::from_value(json!({
+            "bids": [["100", "1"]],
+            "asks": [["101", "1"]],
+        }))
+        .unwrap();
+        let order_book = OrderBook::from(&data);
+        let book_stats = BookStats::compute(&order_book, DEPTH_LEVELS).unwrap();
+        let trade_tape = TradeTape::new(Duration::from_secs(60));
+        let mut snapshot = Snapshot {
+            symbol: "btc_jpy",
+            timestamp: 0,
+            order_book: &order_book,
+            book_stats: &book_stats,
+            trade_tape: &trade_tape,
+            best_bid: "100".parse().unwrap(),
+            best_ask: "101".parse().unwrap(),
+            fees: Default::default(),
+            btc_balance: Size::ZERO,
+            buy_orders: &[],
+            sell_orders: &[],
+        };
+        let config = Config::new("btc_jpy", 0.02, 1.0, 0.00000001).unwrap();
+        let mut strategy = JoinBest::new(&config, Size::from_f64(0.005));
+
+        let quotes = strategy.quotes(&snapshot);
+        assert_eq!(quotes.len(), 1);
+        assert_eq!(quotes[0].side, Side::Buy);
+        assert_eq!(quotes[0].price, "100".parse().unwrap());
+
+        snapshot.btc_balance = Size::from_f64(0.02);
+        let quotes = strategy.quotes(&snapshot);
+        assert_eq!(
+            quotes,
+            vec![Quote {
+                side: Side::Sell,
+                price: "101".parse().unwrap(),
+                size: Size::from_f64(0.02),
+            }]
+        );
+    }
+}
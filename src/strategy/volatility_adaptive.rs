@@ -0,0 +1,121 @@
+use super::{Quote, Side, Snapshot, Strategy};
+use crate::{
+    bus::MarketEvent,
+    config::Config,
+    decimal::{Price, Size},
+    exchanges::coincheck::{subscriptions::SubscriptionHandle, Fill},
+    volatility::{Regime, VolatilityEstimator, VolatilityRules},
+};
+use log::{debug, warn};
+
+// Wraps another strategy and adapts its quotes to the volatility of the mid: quotes move away
+// from the mid and shrink as volatility rises, and are withdrawn entirely in fast markets
+pub struct VolatilityAdaptive {
+    pub inner: Box<dyn Strategy>,
+    pub rules: VolatilityRules,
+    pub estimator: VolatilityEstimator,
+    pub price_increment: Price,
+    pub size_increment: Size,
+    pub min_order_size: Size,
+    paused: bool,
+}
+
+impl VolatilityAdaptive {
+    pub fn new(
+        inner: Box<dyn Strategy>,
+        config: &Config,
+        rules: VolatilityRules,
+        min_order_size: Size,
+    ) -> VolatilityAdaptive {
+        VolatilityAdaptive {
+            inner,
+            rules,
+            estimator: VolatilityEstimator::new(
+                rules.sample_interval,
+                rules.half_life,
+                rules.min_samples,
+            ),
+            price_increment: config.price_increment,
+            size_increment: config.size_increment,
+            min_order_size,
+            paused: false,
+        }
+    }
+
+    // Move a quote away from the mid and shrink it, or drop it if it becomes too small
+    fn adjust(
+        &self,
+        quote: Quote,
+        mid: f64,
+        spread_multiplier: f64,
+        size_factor: f64,
+    ) -> Option<Quote> {
+        let tick = self.price_increment;
+        let price = match quote.side {
+            Side::Buy => Price::from_f64(mid - (mid - quote.price.to_f64()) * spread_multiplier)
+                .floor_to(tick),
+            Side::Sell => Price::from_f64(mid + (quote.price.to_f64() - mid) * spread_multiplier)
+                .ceil_to(tick),
+        };
+        let size = Size::from_f64(quote.size.to_f64() * size_factor).floor_to(self.size_increment);
+        (size >= self.min_order_size).then_some(Quote {
+            price,
+            size,
+            ..quote
+        })
+    }
+}
+
+impl Strategy for VolatilityAdaptive {
+    fn name(&self) -> &str {
+        self.inner.name()
+    }
+
+    fn quotes(&mut self, snapshot: &Snapshot) -> Vec<Quote> {
+        let mid = (snapshot.best_bid.to_f64() + snapshot.best_ask.to_f64()) / 2.0;
+        self.estimator.update(snapshot.timestamp, mid);
+        let quotes = self.inner.quotes(snapshot);
+        let Some(volatility) = self.estimator.volatility_bps() else {
+            return quotes;
+        };
+
+        match self.rules.regime(volatility) {
+            Regime::Pause => {
+                if !self.paused {
+                    warn!("volatility {:.1} bps: pausing quoting", volatility);
+                    self.paused = true;
+                }
+                Vec::new()
+            }
+            Regime::Quote {
+                spread_multiplier,
+                size_factor,
+            } => {
+                if self.paused {
+                    warn!("volatility {:.1} bps: resuming quoting", volatility);
+                    self.paused = false;
+                }
+                debug!(
+                    "volatility {:.1} bps: spread x{:.2}, size x{:.2}",
+                    volatility, spread_multiplier, size_factor
+                );
+                quotes
+                    .into_iter()
+                    .filter_map(|quote| self.adjust(quote, mid, spread_multiplier, size_factor))
+                    .collect()
+            }
+        }
+    }
+
+    fn on_fill(&mut self, fill: &Fill) {
+        self.inner.on_fill(fill);
+    }
+
+    fn on_start(&mut self, subscriptions: SubscriptionHandle) {
+        self.inner.on_start(subscriptions);
+    }
+
+    fn on_market_event(&mut self, event: &MarketEvent) {
+        self.inner.on_market_event(event);
+    }
+}
@@ -0,0 +1,153 @@
+use crate::{
+    decimal::{Price, Size},
+    exchanges::coincheck::Execution,
+};
+use std::{
+    collections::{HashMap, VecDeque},
+    time::Duration,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct TapeTrade {
+    timestamp: u64,
+    price: Price,
+    size: Size,
+    // Whether the aggressor was buying
+    buy: bool,
+}
+
+// Statistics over the trades of a pair within a window
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct TradeStats {
+    pub count: usize,
+    pub volume: Size,
+    // Volume of trades where the taker bought
+    pub buy_volume: Size,
+    // Volume of trades where the taker sold
+    pub sell_volume: Size,
+    pub vwap: Option<f64>,
+    // Square root of the sum of squared log returns between consecutive trades, not annualized
+    pub volatility: Option<f64>,
+}
+
+// Rolling windows of trades per pair, keyed by exchange time in milliseconds
+pub struct TradeTape {
+    // Trades older than this, relative to the newest trade, are dropped
+    horizon: Duration,
+    trades: HashMap<String, VecDeque<TapeTrade>>,
+}
+
+impl TradeTape {
+    pub fn new(horizon: Duration) -> TradeTape {
+        TradeTape {
+            horizon,
+            trades: HashMap::new(),
+        }
+    }
+
+    pub fn add(&mut self, execution: &Execution) {
+        let trades = self.trades.entry(execution.pair.clone()).or_default();
+        trades.push_back(TapeTrade {
+            timestamp: execution.timestamp,
+            price: execution.price,
+            size: execution.size,
+            buy: execution.taker_side == "buy",
+        });
+
+        let cutoff = execution
+            .timestamp
+            .saturating_sub(self.horizon.as_millis() as u64);
+        while trades.front().is_some_and(|trade| trade.timestamp < cutoff) {
+            trades.pop_front();
+        }
+    }
+
+    // Price of the most recent trade of a pair
+    pub fn last_price(&self, pair: &str) -> Option<Price> {
+        self.trades.get(pair)?.back().map(|trade| trade.price)
+    }
+
+    // Statistics over the trades of a pair in the `window` before `now` (milliseconds)
+    pub fn stats(&self, pair: &str, window: Duration, now: u64) -> TradeStats {
+        let cutoff = now.saturating_sub(window.as_millis() as u64);
+        let mut stats = TradeStats::default();
+        let mut notional = 0.0;
+        let mut squared_returns = 0.0;
+        let mut previous_price: Option<f64> = None;
+
+        let trades = self.trades.get(pair).into_iter().flatten();
+        for trade in trades.filter(|trade| trade.timestamp >= cutoff && trade.timestamp <= now) {
+            stats.count += 1;
+            stats.volume += trade.size;
+            if trade.buy {
+                stats.buy_volume += trade.size;
+            } else {
+                stats.sell_volume += trade.size;
+            }
+            let price = trade.price.to_f64();
+            notional += price * trade.size.to_f64();
+            if let Some(previous_price) = previous_price {
+                squared_returns += (price / previous_price).ln().powi(2);
+            }
+            previous_price = Some(price);
+        }
+
+        if !stats.volume.is_zero() {
+            stats.vwap = Some(notional / stats.volume.to_f64());
+        }
+        if stats.count > 1 {
+            stats.volatility = Some(squared_returns.sqrt());
+        }
+        stats
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn execution(timestamp: u64, price: f64, size: f64, taker_side: &str) -> Execution {
+        Execution {
+            timestamp,
+            id: 0,
+            pair: "btc_jpy".to_string(),
+            price: Price::from_f64(price),
+            size: Size::from_f64(size),
+            taker_side: taker_side.to_string(),
+            taker_id: 0,
+            maker_id: 0,
+        }
+    }
+
+    #[test]
+    fn computes_window_stats() {
+        let mut tape = TradeTape::new(Duration::from_secs(60));
+        tape.add(&execution(0, 90.0, 5.0, "buy"));
+        tape.add(&execution(50_000, 100.0, 1.0, "buy"));
+        tape.add(&execution(55_000, 110.0, 3.0, "sell"));
+
+        let stats = tape.stats("btc_jpy", Duration::from_secs(10), 55_000);
+
+        assert_eq!(stats.count, 2);
+        assert_eq!(stats.volume, Size::from_f64(4.0));
+        assert_eq!(stats.buy_volume, Size::from_f64(1.0));
+        assert_eq!(stats.sell_volume, Size::from_f64(3.0));
+        assert_eq!(stats.vwap, Some(107.5));
+        assert_eq!(stats.volatility, Some((110.0_f64 / 100.0).ln()));
+        assert_eq!(
+            tape.stats("eth_jpy", Duration::from_secs(10), 55_000).count,
+            0
+        );
+    }
+
+    #[test]
+    fn drops_trades_beyond_the_horizon() {
+        let mut tape = TradeTape::new(Duration::from_secs(60));
+        tape.add(&execution(0, 90.0, 5.0, "buy"));
+        tape.add(&execution(61_000, 100.0, 1.0, "buy"));
+
+        let stats = tape.stats("btc_jpy", Duration::from_secs(120), 61_000);
+
+        assert_eq!(stats.count, 1);
+    }
+}
@@ -1,4 +1,4 @@
-use anyhow::Result;
+use anyhow::{anyhow, Result};
 use dotenv::dotenv;
 use ring::hmac;
 use std::env;
@@ -29,3 +29,80 @@ pub fn get_timestamp() -> Result<u64> {
     let since_epoch = start.duration_since(UNIX_EPOCH)?;
     Ok(since_epoch.as_millis() as u64)
 }
+
+// Function to format a timestamp in milliseconds since the Unix epoch as a UTC date (YYYY-MM-DD)
+pub fn date_from_timestamp(timestamp: u64) -> String {
+    // Convert days since the epoch to a civil date (Howard Hinnant's algorithm)
+    let days = (timestamp / 86_400_000) as i64 + 719_468;
+    let era = days / 146_097;
+    let day_of_era = days - era * 146_097;
+    let year_of_era =
+        (day_of_era - day_of_era / 1_460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_index = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * month_index + 2) / 5 + 1;
+    let month = if month_index < 10 {
+        month_index + 3
+    } else {
+        month_index - 9
+    };
+    let year = year_of_era + era * 400 + i64::from(month <= 2);
+    format!("{:04}-{:02}-{:02}", year, month, day)
+}
+
+// Function to parse a UTC timestamp such as "2015-01-10T05:55:38.000Z" into milliseconds since the Unix epoch
+pub fn timestamp_from_iso8601(text: &str) -> Result<u64> {
+    let invalid = || anyhow!("invalid timestamp: {:?}", text);
+    let (date, time) = text
+        .trim_end_matches('Z')
+        .split_once('T')
+        .ok_or_else(invalid)?;
+
+    let mut date_parts = date.splitn(3, '-').map(str::parse::<i64>);
+    let (year, month, day) = match (date_parts.next(), date_parts.next(), date_parts.next()) {
+        (Some(Ok(year)), Some(Ok(month)), Some(Ok(day))) => (year, month, day),
+        _ => return Err(invalid()),
+    };
+    let (time, millis) = time.split_once('.').unwrap_or((time, "0"));
+    let mut time_parts = time.splitn(3, ':').map(str::parse::<i64>);
+    let (hour, minute, second) = match (time_parts.next(), time_parts.next(), time_parts.next()) {
+        (Some(Ok(hour)), Some(Ok(minute)), Some(Ok(second))) => (hour, minute, second),
+        _ => return Err(invalid()),
+    };
+    // Keep millisecond precision from the fractional seconds
+    let millis: i64 = format!("{:0<3}", millis)[..3].parse()?;
+
+    // Convert the civil date to days since the epoch (Howard Hinnant's algorithm)
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let year_of_era = year - era * 400;
+    let day_of_year = (153 * ((month + 9) % 12) + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    let days = era * 146_097 + day_of_era - 719_468;
+
+    let timestamp = ((days * 24 + hour) * 60 + minute) * 60_000 + second * 1_000 + millis;
+    u64::try_from(timestamp).map_err(|_| invalid())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn formats_dates() {
+        assert_eq!(date_from_timestamp(0), "1970-01-01");
+        assert_eq!(date_from_timestamp(951_782_400_000), "2000-02-29");
+        assert_eq!(date_from_timestamp(1_700_000_000_000), "2023-11-14");
+    }
+
+    #[test]
+    fn parses_iso8601_timestamps() -> Result<()> {
+        assert_eq!(timestamp_from_iso8601("1970-01-01T00:00:00.000Z")?, 0);
+        assert_eq!(
+            timestamp_from_iso8601("2023-11-14T22:13:20.5Z")?,
+            1_700_000_000_500
+        );
+        assert!(timestamp_from_iso8601("2023-11-14").is_err());
+        Ok(())
+    }
+}
@@ -0,0 +1,173 @@
+use std::time::Duration;
+
+// Exponentially weighted estimate of the volatility of the mid price. Mids are sampled at most
+// once per `sample_interval` and older returns fade with the given half-life. No estimate is
+// given until `min_samples` returns were seen, so a single noisy tick cannot dominate it.
+#[derive(Debug, Clone)]
+pub struct VolatilityEstimator {
+    pub sample_interval: Duration,
+    pub half_life: Duration,
+    pub min_samples: u32,
+    samples: u32,
+    // (timestamp in milliseconds, mid) of the last sample
+    last_sample: Option<(u64, f64)>,
+    // Variance of log returns per second
+    variance: Option<f64>,
+}
+
+impl VolatilityEstimator {
+    pub fn new(
+        sample_interval: Duration,
+        half_life: Duration,
+        min_samples: u32,
+    ) -> VolatilityEstimator {
+        VolatilityEstimator {
+            sample_interval,
+            half_life,
+            min_samples,
+            samples: 0,
+            last_sample: None,
+            variance: None,
+        }
+    }
+
+    pub fn update(&mut self, timestamp: u64, mid: f64) {
+        if mid <= 0.0 {
+            return;
+        }
+        let Some((last_timestamp, last_mid)) = self.last_sample else {
+            self.last_sample = Some((timestamp, mid));
+            return;
+        };
+        let elapsed = timestamp.saturating_sub(last_timestamp);
+        if elapsed < self.sample_interval.as_millis() as u64 {
+            return;
+        }
+
+        let seconds = elapsed as f64 / 1000.0;
+        let sample = (mid / last_mid).ln().powi(2) / seconds;
+        let decay = 0.5_f64.powf(seconds / self.half_life.as_secs_f64().max(f64::EPSILON));
+        // Weigh the first samples equally rather than letting the first one set the estimate
+        self.samples = self.samples.saturating_add(1);
+        let weight = (1.0 - decay).max(1.0 / self.samples as f64);
+        self.variance = Some(match self.variance {
+            Some(variance) => (1.0 - weight) * variance + weight * sample,
+            None => sample,
+        });
+        self.last_sample = Some((timestamp, mid));
+    }
+
+    // Standard deviation of one-second log returns in basis points, once warmed up
+    pub fn volatility_bps(&self) -> Option<f64> {
+        if self.samples < self.min_samples.max(1) {
+            return None;
+        }
+        self.variance.map(|variance| variance.sqrt() * 10_000.0)
+    }
+}
+
+// How quoting reacts to volatility. Below `calm_bps` nothing changes; between `calm_bps` and
+// `pause_bps` spreads widen and sizes shrink linearly, and from `pause_bps` on quoting stops.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct VolatilityRules {
+    pub calm_bps: f64,
+    pub pause_bps: f64,
+    // Distance from the mid is multiplied by up to this factor just before pausing
+    pub max_spread_multiplier: f64,
+    // Size is multiplied by down to this factor just before pausing
+    pub min_size_factor: f64,
+    pub sample_interval: Duration,
+    pub half_life: Duration,
+    // Returns sampled before the estimate is acted on
+    pub min_samples: u32,
+}
+
+impl Default for VolatilityRules {
+    fn default() -> VolatilityRules {
+        VolatilityRules {
+            calm_bps: 3.0,
+            pause_bps: 15.0,
+            max_spread_multiplier: 3.0,
+            min_size_factor: 0.25,
+            sample_interval: Duration::from_secs(1),
+            half_life: Duration::from_secs(30),
+            min_samples: 10,
+        }
+    }
+}
+
+// Adjustment for the current volatility
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Regime {
+    Quote {
+        spread_multiplier: f64,
+        size_factor: f64,
+    },
+    Pause,
+}
+
+impl VolatilityRules {
+    pub fn regime(&self, volatility_bps: f64) -> Regime {
+        if volatility_bps >= self.pause_bps {
+            return Regime::Pause;
+        }
+        let range = (self.pause_bps - self.calm_bps).max(f64::EPSILON);
+        let t = ((volatility_bps - self.calm_bps) / range).clamp(0.0, 1.0);
+        Regime::Quote {
+            spread_multiplier: 1.0 + t * (self.max_spread_multiplier - 1.0),
+            size_factor: 1.0 - t * (1.0 - self.min_size_factor),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn estimates_volatility_and_picks_regime() {
+        let mut estimator =
+            VolatilityEstimator::new(Duration::from_secs(1), Duration::from_secs(30), 3);
+        estimator.update(0, 10_000.0);
+        // Too soon for a new sample
+        estimator.update(500, 10_100.0);
+        assert_eq!(estimator.volatility_bps(), None);
+        // A 10 bps move every second
+        let mut mid = 10_000.0;
+        for second in 1..=3 {
+            // Not acted on before three returns were seen
+            assert_eq!(estimator.volatility_bps(), None);
+            mid *= (0.001_f64).exp();
+            estimator.update(second * 1_000, mid);
+        }
+        let volatility = estimator.volatility_bps().unwrap();
+        assert!((volatility - 10.0).abs() < 0.01);
+
+        // One noisy return right after startup is averaged with the next ones
+        let mut estimator =
+            VolatilityEstimator::new(Duration::from_secs(1), Duration::from_secs(30), 3);
+        estimator.update(0, 10_000.0);
+        estimator.update(1_000, 10_300.0);
+        estimator.update(2_000, 10_300.0);
+        estimator.update(3_000, 10_300.0);
+        let noisy = (10_300.0_f64 / 10_000.0).ln() * 10_000.0;
+        assert!((estimator.volatility_bps().unwrap() - noisy / 3.0_f64.sqrt()).abs() < 0.01);
+
+        let rules = VolatilityRules::default();
+        assert_eq!(
+            rules.regime(2.0),
+            Regime::Quote {
+                spread_multiplier: 1.0,
+                size_factor: 1.0
+            }
+        );
+        assert_eq!(
+            rules.regime(9.0),
+            Regime::Quote {
+                spread_multiplier: 2.0,
+                size_factor: 0.625
+            }
+        );
+        assert_eq!(rules.regime(volatility * 2.0), Regime::Pause);
+    }
+}